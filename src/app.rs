@@ -1,31 +1,61 @@
 use std::sync::{mpsc, Arc};
+use std::time::Duration;
 
+use futures::future::{self, BoxFuture, Future, FutureExt};
+use futures::stream::StreamExt;
 use log::Level;
 use snafu::ResultExt;
-use tokio::stream::StreamExt;
+use tokio::time::Instant;
 
-use crate::config::Config;
+use crate::auth;
+use crate::cmdline::{self, Setting};
+use crate::config::{Config, SearchConfig};
 use crate::draw;
-use crate::key::{Action, Context as KeyContext};
+use crate::export::{self, Exporter, Progress};
+use crate::history::Histories;
+use crate::jobs::{Job, JobId, Jobs};
+use crate::key::{self, Action, BindingConfig, Context as KeyContext, Mode};
 use crate::logger::Logger;
+use crate::meta::{self, Album, Artist, Track};
+use crate::offline::TrackCache;
 use crate::player::{self, Command};
-use crate::providers::Provider;
-use crate::view::{AlbumSearch, ArtistSearch, MainView, Playlist, TrackList, View};
+use crate::providers::{self, Link, Provider};
+use crate::view::{
+    self, AlbumSearch, ArtistPage, ArtistSearch, Destination, Downloads, Help, HelpSection,
+    JobStatus, Library, MainView, Menu, MenuEntry, Origin, Pages, Playlist, PlaylistSearch,
+    PromptKind, SearchKind, TrackList, View,
+};
 
 struct State {
     provider: Provider,
     player_state: player::State,
     prev_view: Option<View>,
     main_view: MainView,
+    histories: Histories,
+    jobs: Jobs<Outcome>,
+    /// Captcha url of the last request, which is rejected as a bot one
+    captcha: Option<String>,
+    tracks: Arc<TrackCache>,
+    exporter: Exporter,
 }
 
 impl State {
-    fn new(provider: Provider, player_state: player::State) -> Self {
+    fn new(
+        provider: Provider,
+        tracks: Arc<TrackCache>,
+        exporter: Exporter,
+        player_state: player::State,
+    ) -> Self {
         Self {
             provider,
             player_state,
             prev_view: None,
             main_view: MainView::default(),
+            histories: Histories::load(),
+            jobs: Jobs::new(),
+            captcha: None,
+            tracks,
+            exporter,
         }
     }
 
@@ -35,18 +65,100 @@ impl State {
         }
     }
 
+    /// Moves the cursor up, Up at the top of a search view recalls the search history
     fn pointer_up(&mut self) {
-        if let Some(mut cursor) = self.main_view.cursor_mut() {
+        let at_top = self.main_view.cursor() == Some(0);
+        if at_top && self.main_view.search_kind().is_some() {
+            self.history_prev();
+        } else if let Some(mut cursor) = self.main_view.cursor_mut() {
             *cursor = cursor.saturating_sub(1);
         }
     }
 
+    /// Moves the cursor to the next (or previous) filtered item, wrapping around the list
+    fn jump_match(&mut self, forward: bool) {
+        if let Some(mut cursor) = self.main_view.cursor_mut() {
+            let len = cursor.len();
+            *cursor = if forward {
+                (*cursor + 1) % len
+            } else {
+                (*cursor + len - 1) % len
+            };
+        }
+    }
+
+    fn input_mode(&self) -> Mode {
+        if self.main_view.prompt().is_some() {
+            Mode::Prompt
+        } else if !self.main_view.insert_buffer().is_empty() {
+            Mode::Insert
+        } else if self.main_view.filter().is_some() {
+            Mode::Filtered
+        } else {
+            Mode::Normal
+        }
+    }
+
     fn push_char(&mut self, c: char) {
-        self.main_view.insert_buffer_mut().push(c);
+        self.reset_history();
+        self.main_view.insert_buffer_mut().insert(c);
     }
 
     fn backspace(&mut self) {
-        self.main_view.insert_buffer_mut().pop();
+        self.reset_history();
+        self.main_view.insert_buffer_mut().backspace();
+    }
+
+    fn reset_history(&mut self) {
+        if let Some(kind) = self.main_view.search_kind() {
+            self.histories.get_mut(kind).reset();
+        }
+    }
+
+    fn history_prev(&mut self) {
+        if let Some(kind) = self.main_view.search_kind() {
+            let current = self.main_view.insert_buffer().to_string();
+            if let Some(entry) = self.histories.get_mut(kind).prev(&current) {
+                let entry = entry.to_string();
+                self.main_view.insert_buffer_mut().set(entry);
+            }
+        }
+    }
+
+    fn history_next(&mut self) {
+        if let Some(kind) = self.main_view.search_kind() {
+            if let Some(entry) = self.histories.get_mut(kind).next() {
+                self.main_view.insert_buffer_mut().set(entry);
+            }
+        }
+    }
+
+    /// Switches to the view of the search kind, so that the following search would use the text
+    fn prepare_search(&mut self, kind: SearchKind, text: String) {
+        let view: View = match kind {
+            SearchKind::Album => AlbumSearch::default().into(),
+            SearchKind::Track => TrackList::default().into(),
+            SearchKind::Artist => ArtistSearch::default().into(),
+            SearchKind::Playlist => PlaylistSearch::default().into(),
+        };
+        if view.name() != self.main_view.name() {
+            self.update_view(view);
+        }
+        self.main_view.insert_buffer_mut().set(text);
+    }
+
+    /// Saves the player queue as an extended m3u playlist of track pages
+    fn save_playlist(&self, path: &std::path::Path) -> std::io::Result<()> {
+        use std::io::Write;
+
+        let mut file = std::fs::File::create(path)?;
+        writeln!(file, "#EXTM3U")?;
+        for track in self.player_state.lock().unwrap().playlist() {
+            let artists = itertools::join(track.artists.iter().map(|a| a.name.as_str()), ", ");
+            writeln!(file, "#EXTINF:-1,{} - {}", artists, track.name)?;
+            writeln!(file, "{}", providers::track_page_url(track))?;
+        }
+        Ok(())
     }
 
     fn restore_view(&mut self) {
@@ -59,53 +171,96 @@ impl State {
         self.prev_view = self.main_view.replace_view(new_view.into()).into();
     }
 
+    /// Spawns the task as a background job of the current view
+    fn spawn(&mut self, task: Task) -> JobId {
+        self.jobs
+            .spawn(task.name, self.main_view.name(), task.future)
+    }
+
+    fn redraw(
+        &mut self,
+        drawer: &mut draw::Drawer,
+        logger: &mut Logger,
+        case: &'static str,
+    ) -> Result<(), Error> {
+        let jobs = self
+            .jobs
+            .running()
+            .iter()
+            .map(|job| JobStatus {
+                id: job.id,
+                name: job.name.clone(),
+                view: job.view,
+                elapsed: job.elapsed(),
+            })
+            .collect();
+        self.main_view.set_jobs(jobs);
+        let failures = self.player_state.lock().unwrap().take_failures();
+        for failure in failures {
+            logger.log(Level::Error, "player", failure);
+        }
+        let generation = self.tracks.generation();
+        if self.main_view.offline_generation() != Some(generation) {
+            self.main_view.set_offline(generation, self.tracks.ids());
+        }
+        self.main_view.set_logged_in(self.provider.is_logged_in());
+        self.main_view.set_library(self.provider.has_library());
+        if let View::Downloads(downloads) = &mut *self.main_view {
+            downloads.exports = self.exporter.progress();
+        }
+        drawer
+            .redraw(&self.main_view, logger.log_lines())
+            .context(Drawer { case })
+    }
+
     #[allow(clippy::single_match)]
-    async fn switch_to_album_search(&mut self) -> Result<(), crate::providers::Error> {
+    fn switch_to_album_search(&mut self) -> Option<Task> {
         match &mut *self.main_view {
             View::ArtistSearch(search) => {
                 if let Some(artist) = search.cached_artists.get(search.cursor) {
-                    let albums = self.provider.artist_albums(&artist).await?.albums;
-
-                    self.update_view(AlbumSearch::from(albums));
+                    return Some(artist_albums(self.provider.clone(), artist.clone(), false));
                 } else {
                     search.cursor = 0;
                 }
             }
             _ => {}
         }
-        Ok(())
+        None
     }
 
     #[allow(clippy::single_match)]
-    async fn switch_to_track_search(&mut self) -> Result<(), crate::providers::Error> {
+    fn switch_to_track_search(&mut self) -> Option<Task> {
         match &mut *self.main_view {
             View::ArtistSearch(search) => {
                 if let Some(artist) = search.cached_artists.get(search.cursor) {
-                    let tracks = self
-                        .provider
-                        .artist_tracks(&artist)
-                        .await?
-                        .tracks
-                        .into_iter()
-                        .map(|mut track| {
-                            Arc::get_mut(&mut track.artists)
-                                .unwrap()
-                                .insert(0, artist.clone());
-                            track
-                        })
-                        .collect::<Vec<_>>();
-
-                    self.update_view(TrackList::from(tracks));
+                    return Some(artist_tracks(self.provider.clone(), artist.clone(), false));
                 } else {
                     search.cursor = 0;
                 }
             }
             _ => {}
         }
-        Ok(())
+        None
+    }
+
+    fn switch_to_playlist_search(&mut self) -> Option<Task> {
+        match &mut *self.main_view {
+            View::ArtistSearch(search) => match search.cached_artists.get(search.cursor) {
+                Some(artist) => Some(artist_playlists(
+                    self.provider.clone(),
+                    artist.clone(),
+                    false,
+                )),
+                None => {
+                    search.cursor = 0;
+                    None
+                }
+            },
+            _ => None,
+        }
     }
 
-    async fn switch_to_artist(&mut self) -> Result<(), crate::providers::Error> {
+    fn switch_to_artist(&mut self) {
         match &mut *self.main_view {
             View::AlbumSearch(search) => {
                 if let Some(album) = search.cached_albums.get(search.cursor) {
@@ -127,61 +282,819 @@ impl State {
             }
             _ => {}
         }
-        Ok(())
     }
 
-    async fn search(&mut self) -> Result<(), crate::providers::Error> {
-        match self.main_view.view_and_buffer_mut() {
-            (View::ArtistSearch(search), insert_buffer) if !insert_buffer.is_empty() => {
-                search.cached_artists = self.provider.artists_search(&insert_buffer).await?.artists;
-                insert_buffer.clear();
+    /// Kind and text of the search, which might be performed at the current view
+    fn search_query(&self) -> Option<(SearchKind, String)> {
+        let kind = self.main_view.search_kind()?;
+        let text = self.main_view.insert_buffer();
+        if text.is_empty() {
+            None
+        } else {
+            Some((kind, text.to_string()))
+        }
+    }
+
+    /// Live search results are put at the current view and are not stored at history
+    fn search(&self, live: bool) -> Option<Task> {
+        let (kind, query) = self.search_query()?;
+        // a pasted link is opened by the explicit search only, its prefix is not a link yet
+        if live && query.starts_with("https://") {
+            return None;
+        }
+        let provider = self.provider.clone();
+
+        Some(Task::new(format!("search for {}", query), async move {
+            let found = find(provider, kind, &query, 0).await?;
+            Ok(Done::Found { found, query, live })
+        }))
+    }
+
+    /// Requests the next page of the current search results, if the cursor is close to their end
+    fn load_page(&mut self) -> Option<Task> {
+        let kind = self.main_view.search_kind()?;
+        let (query, page) = self.main_view.request_page()?;
+        let provider = self.provider.clone();
+
+        Some(Task::new(
+            format!("page {} of {}", page + 1, query),
+            async move {
+                let found = find(provider, kind, &query, page).await;
+                Ok(Done::Page { found, query, page })
+            },
+        ))
+    }
+
+    /// Puts the results at the current view, unless the view kind is changed since the search.
+    /// Live results do not create a new view. The insert buffer is cleared, if it is not edited
+    /// since the search.
+    fn apply_found(&mut self, found: Found, query: String, live: bool) {
+        match (self.main_view.view_and_buffer_mut(), found) {
+            ((View::ArtistSearch(search), insert_buffer), Found::Artists(artists)) => {
+                *search = ArtistSearch::from(artists);
+                if !live && query == **insert_buffer {
+                    insert_buffer.clear();
+                }
+            }
+            ((View::AlbumSearch(search), insert_buffer), Found::Albums(albums)) => {
+                *search = AlbumSearch::from(albums);
+                if !live && query == **insert_buffer {
+                    insert_buffer.clear();
+                }
+            }
+            ((View::PlaylistSearch(search), insert_buffer), Found::Playlists(playlists)) => {
+                *search = PlaylistSearch::from(playlists);
+                if !live && query == **insert_buffer {
+                    insert_buffer.clear();
+                }
             }
-            (View::AlbumSearch(search), insert_buffer) if !insert_buffer.is_empty() => {
-                search.cached_albums = self.provider.album_search(&insert_buffer).await?.albums;
-                insert_buffer.clear();
+            ((View::TrackList(list), _), Found::Tracks(tracks)) if live => {
+                *list = TrackList::from(tracks);
             }
-            (View::TrackList(_), insert_buffer) if !insert_buffer.is_empty() => {
-                let tracks = self.provider.track_search(insert_buffer).await?.tracks;
+            ((View::TrackList(_), _), Found::Tracks(tracks)) => {
                 self.update_view(TrackList::from(tracks));
             }
-            _ => {}
+            _ => {
+                log::debug!("search results are dropped due to the view change");
+                return;
+            }
+        }
+
+        if let Some(origin) = self.main_view.origin_mut() {
+            *origin = Some(Origin::Search(query.clone()));
+        }
+        if let Some(pages) = self.main_view.pages_mut() {
+            *pages = Some(Pages::new(query));
         }
-        Ok(())
     }
 
-    async fn select(&mut self) -> Result<Option<Command>, crate::providers::Error> {
-        match &mut *self.main_view {
-            View::AlbumSearch(search) if search.cursor < search.cached_albums.len() => {
-                let album = &search.cached_albums[search.cursor];
-                let tracks = self
-                    .provider
-                    .album_tracks(&album)
-                    .await?
-                    .tracks
-                    .into_iter()
-                    .map(|mut track| {
-                        let track_artists = Arc::get_mut(&mut track.artists).unwrap();
-                        // XXX: quadratic complexity here, though maybe ok due to small sizes
-                        for album_artist in album.artists.iter() {
-                            if !track_artists.iter().any(|x| x.name == album_artist.name) {
-                                track_artists.push(album_artist.clone());
+    /// Appends the page to the current view, if it still shows the results of the query
+    fn append_page(&mut self, found: Found, query: &str, page: usize) {
+        let expected = self
+            .main_view
+            .pages()
+            .map(|pages| (pages.query.as_str(), pages.next));
+        if expected != Some((query, page)) {
+            log::debug!(
+                "page {} of {} is dropped due to the view change",
+                page,
+                query
+            );
+            return;
+        }
+
+        let appended = match (&mut *self.main_view, found) {
+            (View::ArtistSearch(search), Found::Artists(artists)) => {
+                let appended = artists.len();
+                search.cached_artists.extend(artists);
+                appended
+            }
+            (View::AlbumSearch(search), Found::Albums(albums)) => {
+                let appended = albums.len();
+                search.cached_albums.extend(albums);
+                appended
+            }
+            (View::TrackList(list), Found::Tracks(tracks)) => {
+                let appended = tracks.len();
+                list.cached_tracks.extend(tracks);
+                appended
+            }
+            (View::PlaylistSearch(search), Found::Playlists(playlists)) => {
+                let appended = playlists.len();
+                search.cached_playlists.extend(playlists);
+                appended
+            }
+            _ => 0,
+        };
+
+        if let Some(slot) = self.main_view.pages_mut() {
+            // an empty page means there are no more results
+            if appended == 0 {
+                *slot = None;
+            } else if let Some(pages) = slot {
+                pages.next += 1;
+                pages.loading = false;
+            }
+        }
+        if let Some(pattern) = self.main_view.filter().map(|filter| filter.pattern.clone()) {
+            self.main_view.set_filter(&pattern);
+        }
+    }
+
+    /// Stops paging the query, so that the failed page is not requested on every key press.
+    /// Refreshing the view starts paging again.
+    fn page_failed(&mut self, query: &str, page: usize) {
+        if let Some(slot) = self.main_view.pages_mut() {
+            let failed = matches!(slot, Some(pages) if pages.query == query && pages.next == page);
+            if failed {
+                *slot = None;
+            }
+        }
+    }
+
+    /// Shows the failure, the captcha is remembered to be opened by `:captcha`
+    fn job_failed(&mut self, logger: &mut Logger, job: &Job, err: providers::Error) {
+        match err {
+            providers::Error::Captcha { url } => {
+                let line = "yandex asks to solve a captcha: `:captcha` opens it in a \
+                            browser, `:cookie <cookie>` sets the session cookie";
+                logger.log(Level::Warn, &format!("{} failed", job.name), line);
+                self.captcha = Some(url);
+            }
+            err => {
+                let line = format!("{} failed: {}", job.name, err);
+                logger.log(Level::Error, "job failed", line);
+            }
+        }
+    }
+
+    fn select(&mut self) -> Option<Task> {
+        match &*self.main_view {
+            View::AlbumSearch(search) => {
+                let album = search.cached_albums.get(search.cursor)?.clone();
+                Some(album_tracks(self.provider.clone(), album, false))
+            }
+            View::PlaylistSearch(search) => {
+                let playlist = search.cached_playlists.get(search.cursor)?.clone();
+                Some(playlist_tracks(self.provider.clone(), playlist, false))
+            }
+            View::ArtistSearch(search) => {
+                let artist = search.cached_artists.get(search.cursor)?.clone();
+                Some(artist_page(self.provider.clone(), artist, false))
+            }
+            _ => None,
+        }
+    }
+
+    /// Adds the selected track, album or artist to the library or removes it from there
+    fn like(&self, liked: bool) -> Option<Task> {
+        let item = match &*self.main_view {
+            View::TrackList(list) => {
+                meta::Likeable::Track(list.cached_tracks.get(list.cursor)?.clone())
+            }
+            View::AlbumSearch(search) => {
+                meta::Likeable::Album(search.cached_albums.get(search.cursor)?.clone())
+            }
+            View::ArtistSearch(search) => {
+                meta::Likeable::Artist(search.cached_artists.get(search.cursor)?.clone())
+            }
+            View::ArtistPage(page) => meta::Likeable::Artist(page.page.artist.clone()),
+            _ => return None,
+        };
+
+        let provider = self.provider.clone();
+        let task = if liked {
+            format!("like of {}", item.name())
+        } else {
+            format!("unlike of {}", item.name())
+        };
+        Some(Task::new(task, async move {
+            provider.set_liked(&item, liked).await?;
+            Ok(Done::Liked {
+                item: Box::new(item),
+                liked,
+            })
+        }))
+    }
+
+    /// Station of the selected track or artist, the recommendations are played otherwise
+    fn station(&self) -> meta::Station {
+        match &*self.main_view {
+            View::TrackList(list) => list
+                .cached_tracks
+                .get(list.cursor)
+                .map(meta::Station::of_track),
+            View::ArtistSearch(search) => search
+                .cached_artists
+                .get(search.cursor)
+                .map(meta::Station::of_artist),
+            View::ArtistPage(page) => Some(meta::Station::of_artist(&page.page.artist)),
+            _ => None,
+        }
+        .unwrap_or_else(meta::Station::my_wave)
+    }
+
+    /// Loads the current list again, bypassing the cache
+    fn refresh(&self) -> Option<Task> {
+        let provider = self.provider.fresh();
+        let task = match (self.main_view.view(), self.main_view.origin()?.clone()) {
+            (view, Origin::Search(query)) => {
+                let kind = view.search_kind()?;
+                Task::new(format!("refresh of {}", query), async move {
+                    let found = find(provider, kind, &query, 0).await?;
+                    Ok(Done::Found {
+                        found,
+                        query,
+                        live: true,
+                    })
+                })
+            }
+            (View::AlbumSearch(_), Origin::Artist(artist)) => artist_albums(provider, artist, true),
+            (View::TrackList(_), Origin::Artist(artist)) => artist_tracks(provider, artist, true),
+            (View::PlaylistSearch(_), Origin::Artist(artist)) => {
+                artist_playlists(provider, artist, true)
+            }
+            (View::ArtistPage(_), Origin::Artist(artist)) => artist_page(provider, artist, true),
+            (View::TrackList(_), Origin::Album(album)) => album_tracks(provider, album, true),
+            (View::TrackList(_), Origin::Playlist(playlist)) => {
+                playlist_tracks(provider, playlist, true)
+            }
+            (_, Origin::Library(section)) => library(provider, section, true),
+            (_, Origin::Destination(destination)) => discover(provider, destination, true),
+            _ => return None,
+        };
+        Some(task)
+    }
+
+    /// Replaces the current list with the refreshed one, unless the view is changed since
+    fn apply_refreshed(&mut self, view: View) {
+        if view.name() != self.main_view.name() {
+            log::debug!("refreshed list is dropped due to the view change");
+            return;
+        }
+        let cursor = self.main_view.cursor();
+        self.main_view.replace_view(view);
+        if let (Some(position), Some(mut cursor)) = (cursor, self.main_view.cursor_mut()) {
+            *cursor = position;
+        }
+    }
+
+    /// Downloads the tracks of the current list, which are not downloaded yet
+    fn download(&self, concurrency: usize) -> Option<Task> {
+        let tracks = match &*self.main_view {
+            View::TrackList(list) => &list.cached_tracks,
+            View::Playlist(playlist) => &playlist.tracks,
+            _ => return None,
+        };
+        let tracks = tracks
+            .iter()
+            .filter(|track| !self.tracks.contains(track.track_id))
+            .cloned()
+            .collect::<Vec<_>>();
+        if tracks.is_empty() {
+            return None;
+        }
+
+        let total = tracks.len();
+        let provider = self.provider.clone();
+        let cache = self.tracks.clone();
+        Some(Task::new(
+            format!("download of {} tracks", total),
+            async move {
+                let downloaded = futures::stream::iter(tracks)
+                    .map(|track| {
+                        let provider = provider.clone();
+                        let cache = cache.clone();
+                        async move {
+                            let stored = match provider.download_track(&track).await {
+                                Ok(data) => cache.put(track.track_id, &data),
+                                Err(err) => {
+                                    log::error!("cannot download {}: {}", track.name, err);
+                                    return false;
+                                }
+                            };
+                            if let Err(ref err) = stored {
+                                log::error!("cannot store {}: {}", track.name, err);
                             }
+                            stored.is_ok()
                         }
-                        track
                     })
-                    .collect::<Vec<_>>();
+                    .buffer_unordered(concurrency)
+                    .fold(0, |downloaded, stored| {
+                        future::ready(downloaded + stored as usize)
+                    })
+                    .await;
+                Ok(Done::Downloaded { downloaded, total })
+            },
+        ))
+    }
 
-                self.update_view(TrackList::from(tracks));
+    /// Exports the selected album, discography of the selected artist or track of an album
+    fn export(&self) -> Option<Task> {
+        let (name, request) = match &*self.main_view {
+            View::AlbumSearch(search) => {
+                let album = search.cached_albums.get(search.cursor)?.clone();
+                let name = album.title.clone();
+                (
+                    name,
+                    export::Request::Album {
+                        album,
+                        picked: None,
+                    },
+                )
             }
-            View::TrackList(search) if search.cursor < search.cached_tracks.len() => {
-                let track = search.cached_tracks[search.cursor].clone();
-                let url = self.provider.get_track_url(&track).await?;
-                log::info!("{:?}; {:?}", track, url);
-                return Ok(Some(Command::Enqueue { track, url }));
+            View::ArtistSearch(search) => {
+                let artist = search.cached_artists.get(search.cursor)?.clone();
+                let name = format!("discography of {}", artist.name);
+                (name, export::Request::Discography(artist))
             }
-            _ => {}
+            View::TrackList(list) => match list.origin {
+                Some(Origin::Album(ref album)) => {
+                    let track = list.cached_tracks.get(list.cursor)?;
+                    let request = export::Request::Album {
+                        album: album.clone(),
+                        picked: Some(vec![track.track_id]),
+                    };
+                    (track.name.clone(), request)
+                }
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        let exporter = self.exporter.clone();
+        Some(Task::new(format!("export of {}", name), async move {
+            Ok(Done::Exported(exporter.export(name, request).await?))
+        }))
+    }
+
+    fn selected_entry(&self) -> Option<MenuEntry> {
+        match *self.main_view {
+            View::Menu(ref menu) => menu.items.get(menu.cursor).cloned(),
+            _ => None,
+        }
+    }
+
+    /// List of the selected section of the artist page
+    fn selected_section(&self) -> Option<View> {
+        match *self.main_view {
+            View::ArtistPage(ref page) => {
+                let section = page.sections.get(page.cursor)?;
+                Some(page.section_view(*section))
+            }
+            _ => None,
+        }
+    }
+
+    fn selected_track(&self) -> Option<&Track> {
+        match *self.main_view {
+            View::TrackList(ref list) => list.cached_tracks.get(list.cursor),
+            _ => None,
+        }
+    }
+}
+
+fn create_help(binding: &BindingConfig) -> Help {
+    let names = |mode, context, action| {
+        let mut names: Vec<_> = binding
+            .events(mode, context, action)
+            .iter()
+            .map(key::event_name)
+            .collect();
+        // e.g. both `\n` and `\r` are named Enter
+        names.dedup();
+        names
+    };
+
+    let views = KeyContext::views()
+        .iter()
+        .map(|(title, context)| HelpSection {
+            title,
+            bindings: Action::bindable()
+                .map(|action| (action.name(), names(Mode::Normal, *context, action)))
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+    // the modes list only the keys, which differ from the normal ones
+    let modes = Mode::specific()
+        .iter()
+        .map(|(title, mode)| HelpSection {
+            title,
+            bindings: Action::bindable()
+                .filter_map(|action| {
+                    let events = names(*mode, KeyContext::search(), action);
+                    let normal = names(Mode::Normal, KeyContext::search(), action);
+                    if events.is_empty() || events == normal {
+                        None
+                    } else {
+                        Some((action.name(), events))
+                    }
+                })
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+    Help::create(views.into_iter().chain(modes).collect())
+}
+
+/// Search results, which are not yet put at a view
+enum Found {
+    Artists(Vec<Artist>),
+    Albums(Vec<Album>),
+    Tracks(Vec<Track>),
+    Playlists(Vec<meta::Playlist>),
+}
+
+impl Found {
+    fn kind(&self) -> SearchKind {
+        match self {
+            Found::Artists(_) => SearchKind::Artist,
+            Found::Albums(_) => SearchKind::Album,
+            Found::Tracks(_) => SearchKind::Track,
+            Found::Playlists(_) => SearchKind::Playlist,
+        }
+    }
+}
+
+async fn find(
+    provider: Provider,
+    kind: SearchKind,
+    text: &str,
+    page: usize,
+) -> Result<Found, providers::Error> {
+    let found = match kind {
+        SearchKind::Artist => Found::Artists(provider.artists_search(text, page).await?.artists),
+        SearchKind::Album => Found::Albums(provider.album_search(text, page).await?.albums),
+        SearchKind::Track => Found::Tracks(provider.track_search(text, page).await?.tracks),
+        SearchKind::Playlist => {
+            Found::Playlists(provider.playlist_search(text, page).await?.playlists)
+        }
+    };
+    Ok(found)
+}
+
+/// Results of the background jobs
+enum Done {
+    /// The first page of the search results
+    Found {
+        found: Found,
+        query: String,
+        live: bool,
+    },
+    Page {
+        found: Result<Found, providers::Error>,
+        query: String,
+        page: usize,
+    },
+    /// A list, which is opened as a new view or replaces the refreshed current one
+    List {
+        view: Box<View>,
+        refresh: bool,
+    },
+    Downloaded {
+        downloaded: usize,
+        total: usize,
+    },
+    Exported(Progress),
+    Liked {
+        item: Box<meta::Likeable>,
+        liked: bool,
+    },
+    /// The track of a link, it is enqueued and shown among its album tracks
+    Play {
+        view: Box<View>,
+        track: Box<Track>,
+    },
+}
+
+type Outcome = Result<Done, providers::Error>;
+
+fn artist_albums(provider: Provider, artist: Artist, refresh: bool) -> Task {
+    Task::new(format!("albums of {}", artist.name), async move {
+        let mut view = AlbumSearch::from(provider.artist_albums(&artist).await?.albums);
+        view.origin = Some(Origin::Artist(artist));
+        Ok(Done::List {
+            view: Box::new(view.into()),
+            refresh,
+        })
+    })
+}
+
+fn artist_tracks(provider: Provider, artist: Artist, refresh: bool) -> Task {
+    Task::new(format!("tracks of {}", artist.name), async move {
+        let tracks = provider
+            .artist_tracks(&artist)
+            .await?
+            .tracks
+            .into_iter()
+            .map(|track| track.with_artist(&artist))
+            .collect::<Vec<_>>();
+        let mut view = TrackList::from(tracks);
+        view.origin = Some(Origin::Artist(artist));
+        Ok(Done::List {
+            view: Box::new(view.into()),
+            refresh,
+        })
+    })
+}
+
+fn artist_playlists(provider: Provider, artist: Artist, refresh: bool) -> Task {
+    Task::new(format!("playlists of {}", artist.name), async move {
+        let playlists = provider.artist_playlists(&artist).await?.playlists;
+        let mut view = PlaylistSearch::from(playlists);
+        view.origin = Some(Origin::Artist(artist));
+        Ok(Done::List {
+            view: Box::new(view.into()),
+            refresh,
+        })
+    })
+}
+
+fn artist_page(provider: Provider, artist: Artist, refresh: bool) -> Task {
+    Task::new(format!("page of {}", artist.name), async move {
+        let mut view = ArtistPage::from(provider.artist_page(&artist).await?);
+        view.origin = Some(Origin::Artist(artist));
+        Ok(Done::List {
+            view: Box::new(view.into()),
+            refresh,
+        })
+    })
+}
+
+fn album_tracks(provider: Provider, album: Album, refresh: bool) -> Task {
+    Task::new(format!("tracks of {}", album.title), async move {
+        let tracks = provider
+            .album_tracks(&album)
+            .await?
+            .tracks
+            .into_iter()
+            .map(|mut track| {
+                let track_artists = Arc::get_mut(&mut track.artists).unwrap();
+                // XXX: quadratic complexity here, though maybe ok due to small sizes
+                for album_artist in album.artists.iter() {
+                    if !track_artists.iter().any(|x| x.name == album_artist.name) {
+                        track_artists.push(album_artist.clone());
+                    }
+                }
+                track
+            })
+            .collect::<Vec<_>>();
+        let mut view = TrackList::from(tracks);
+        view.origin = Some(Origin::Album(album));
+        Ok(Done::List {
+            view: Box::new(view.into()),
+            refresh,
+        })
+    })
+}
+
+/// Opens the item of the link, the linked track is played as well
+fn open_link(provider: Provider, link: Link) -> Task {
+    match link {
+        Link::Artist(id) => {
+            // the name is taken from the artist page
+            let artist = Artist {
+                id,
+                name: id.to_string(),
+            };
+            artist_page(provider, artist, false)
+        }
+        Link::Album(id) => {
+            let album = Album::new(id, id.to_string());
+            album_tracks(provider, album, false)
+        }
+        Link::Playlist { owner, kind } => {
+            let playlist = meta::Playlist {
+                url: format!("/users/{}/playlists/{}", owner, kind),
+                owner,
+                title: kind.to_string(),
+                track_count: 0,
+            };
+            playlist_tracks(provider, playlist, false)
+        }
+        Link::Track { album, track } => Task::new(format!("track {}", track), async move {
+            let tracks = match album {
+                Some(album) => {
+                    let album = Album::new(album, String::new());
+                    provider.album_tracks(&album).await?.tracks
+                }
+                None => provider.tracks_by_ids(&[track]).await?.tracks,
+            };
+            let position = tracks
+                .iter()
+                .position(|found| found.track_id == track)
+                .ok_or_else(|| providers::Error::NotFound { url: track.path() })?;
+            let played = tracks[position].clone();
+            let mut view = TrackList::from(tracks);
+            view.cursor = position;
+            Ok(Done::Play {
+                view: Box::new(view.into()),
+                track: Box::new(played),
+            })
+        }),
+    }
+}
+
+fn playlist_tracks(provider: Provider, playlist: meta::Playlist, refresh: bool) -> Task {
+    Task::new(format!("tracks of {}", playlist.title), async move {
+        let mut view = TrackList::from(provider.playlist_tracks(&playlist).await?.tracks);
+        view.origin = Some(Origin::Playlist(playlist));
+        Ok(Done::List {
+            view: Box::new(view.into()),
+            refresh,
+        })
+    })
+}
+
+/// Loads the section of the library of the logged in user
+fn library(provider: Provider, section: Library, refresh: bool) -> Task {
+    let name = match section {
+        Library::Tracks => "liked tracks",
+        Library::Albums => "liked albums",
+        Library::Artists => "followed artists",
+        Library::Playlists => "own playlists",
+    };
+    Task::new(name.to_string(), async move {
+        let mut view: View = match section {
+            Library::Tracks => TrackList::from(provider.liked_tracks().await?.tracks).into(),
+            Library::Albums => AlbumSearch::from(provider.liked_albums().await?.albums).into(),
+            Library::Artists => ArtistSearch::from(provider.liked_artists().await?.artists).into(),
+            Library::Playlists => {
+                PlaylistSearch::from(provider.user_playlists().await?.playlists).into()
+            }
+        };
+        if let Some(origin) = view.origin_mut() {
+            *origin = Some(Origin::Library(section));
+        }
+        Ok(Done::List {
+            view: Box::new(view),
+            refresh,
+        })
+    })
+}
+
+/// Loads the list of the destination
+fn discover(provider: Provider, destination: Destination, refresh: bool) -> Task {
+    let name = view::destination_label(&destination).to_lowercase();
+    Task::new(name, async move {
+        let mut view: View = match destination {
+            Destination::Chart => TrackList::from(provider.chart().await?.tracks).into(),
+            Destination::NewReleases => {
+                AlbumSearch::from(provider.new_releases().await?.albums).into()
+            }
+            Destination::NewPlaylists => {
+                PlaylistSearch::from(provider.new_playlists().await?.playlists).into()
+            }
+            Destination::Genres => {
+                let genres = provider.genres().await?.genres;
+                Menu::create(
+                    "Genres".to_string(),
+                    genres.into_iter().map(MenuEntry::Genre).collect(),
+                )
+                .into()
+            }
+            Destination::GenreTracks(ref genre) => {
+                TrackList::from(provider.genre_tracks(genre).await?.tracks).into()
+            }
+            Destination::GenreAlbums(ref genre) => {
+                AlbumSearch::from(provider.genre_albums(genre).await?.albums).into()
+            }
+        };
+        if let Some(origin) = view.origin_mut() {
+            *origin = Some(Origin::Destination(destination));
+        }
+        Ok(Done::List {
+            view: Box::new(view),
+            refresh,
+        })
+    })
+}
+
+/// Provider work, which is performed in background, while the UI stays responsive
+struct Task {
+    name: String,
+    future: BoxFuture<'static, Outcome>,
+}
+
+impl Task {
+    fn new(name: String, future: impl Future<Output = Outcome> + Send + 'static) -> Self {
+        Self {
+            name,
+            future: future.boxed(),
+        }
+    }
+}
+
+const SPINNER_TICK: Duration = Duration::from_millis(100);
+
+/// Player failures are checked that often, while there are no other events
+const PLAYER_POLL: Duration = Duration::from_millis(500);
+
+async fn spinner_tick(loading: bool) {
+    if loading {
+        tokio::time::delay_for(SPINNER_TICK).await
+    } else {
+        future::pending().await
+    }
+}
+
+/// Search performed after a pause in typing, starting a newer one cancels the older
+struct LiveSearch {
+    enabled: bool,
+    delay: Duration,
+    due: Option<Instant>,
+    job: Option<JobId>,
+}
+
+impl LiveSearch {
+    fn new(config: &SearchConfig) -> Self {
+        Self {
+            enabled: config.live,
+            delay: config.delay,
+            due: None,
+            job: None,
+        }
+    }
+
+    fn schedule(&mut self, jobs: &mut Jobs<Outcome>) {
+        self.cancel(jobs);
+        self.due = Some(Instant::now() + self.delay);
+    }
+
+    fn start(&mut self, job: JobId) {
+        self.due = None;
+        self.job = Some(job);
+    }
+
+    fn cancel(&mut self, jobs: &mut Jobs<Outcome>) {
+        self.due = None;
+        if let Some(job) = self.job.take() {
+            jobs.cancel(job);
         }
-        Ok(None)
+    }
+
+    /// Waits until a new search should be started
+    async fn wait(&mut self) {
+        match self.due {
+            Some(due) => {
+                tokio::time::delay_until(due).await;
+                self.due = None;
+            }
+            None => future::pending().await,
+        }
+    }
+}
+
+enum Message {
+    Action(Action),
+    SearchDue,
+    Finished(Job, Outcome),
+    Tick,
+    PlayerPoll,
+}
+
+/// Opens the url with `$BROWSER` or the desktop default application
+fn open_in_browser(url: &str) -> std::io::Result<()> {
+    use std::process::{Command, Stdio};
+
+    let browser = std::env::var("BROWSER").unwrap_or_else(|_| "xdg-open".to_string());
+    Command::new(browser)
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(drop)
+}
+
+fn key_context(view: &View) -> KeyContext {
+    match view {
+        View::AlbumSearch(_)
+        | View::ArtistSearch(_)
+        | View::PlaylistSearch(_)
+        | View::Menu(_)
+        | View::ArtistPage(_) => KeyContext::search(),
+        View::TrackList(_) => KeyContext::search() | KeyContext::tracklist(),
+        View::Playlist(_) | View::Downloads(_) => KeyContext::playlist(),
     }
 }
 
@@ -202,22 +1115,29 @@ pub enum Error {
 pub struct App {
     config: Config,
     provider: Provider,
+    tracks: Arc<TrackCache>,
     player_commands: mpsc::Sender<Command>,
     player_state: player::State,
+    /// Link, which is opened at the start
+    link: Option<Link>,
 }
 
 impl App {
     pub fn create(
         config: Config,
         provider: Provider,
+        tracks: Arc<TrackCache>,
         player_commands: mpsc::Sender<Command>,
         player_state: player::State,
+        link: Option<Link>,
     ) -> Result<Self, Error> {
         Ok(Self {
             config,
             provider,
+            tracks,
             player_commands,
             player_state,
+            link,
         })
     }
 
@@ -225,25 +1145,301 @@ impl App {
         let App {
             config,
             provider,
+            tracks,
             player_commands,
             player_state,
+            link,
         } = self;
 
-        let mut state = State::new(provider, player_state);
+        let exporter = Exporter::new(
+            provider.clone(),
+            tracks.clone(),
+            config.export.dir.clone(),
+            config.provider.concurrency,
+        );
+        let mut state = State::new(provider, tracks, exporter, player_state);
         let mut logger = Logger::default();
         let mut drawer = draw::Drawer::new().context(Drawer {
             case: "create context",
         })?;
 
-        drawer
-            .redraw(&state.main_view, logger.log_lines())
-            .context(Drawer {
-                case: "initial draw",
-            })?;
+        if let Some(link) = link {
+            let task = open_link(state.provider.clone(), link);
+            state.spawn(task);
+        }
+        state.redraw(&mut drawer, &mut logger, "initial draw")?;
 
-        let (mut events, current_context) = config.binding.actions();
+        let mut live_search = LiveSearch::new(&config.search);
+        let (mut events, input) = config.binding.actions();
+        let mut queued = None;
+
+        loop {
+            let loading = !state.jobs.is_empty();
+            let message = match queued.take() {
+                Some(action) => Message::Action(action),
+                None => tokio::select! {
+                    action = events.next() => match action {
+                        Some(action) => Message::Action(action),
+                        None => break,
+                    },
+                    _ = live_search.wait() => Message::SearchDue,
+                    (job, outcome) = state.jobs.finished() => Message::Finished(job, outcome),
+                    _ = spinner_tick(loading) => Message::Tick,
+                    _ = tokio::time::delay_for(PLAYER_POLL) => Message::PlayerPoll,
+                },
+            };
+
+            let action = match message {
+                Message::Action(action) => action,
+                Message::SearchDue => {
+                    if let Some(task) = state.search(true) {
+                        let job = state.spawn(task);
+                        live_search.start(job);
+                    }
+                    state.redraw(&mut drawer, &mut logger, "live search start")?;
+                    continue;
+                }
+                Message::Finished(job, outcome) => {
+                    match outcome {
+                        Ok(Done::Found { found, query, live }) => {
+                            let kind = found.kind();
+                            if !live {
+                                logger.log(Level::Info, "ok", "completed");
+                                if let Err(err) = state.histories.get_mut(kind).push(&query) {
+                                    logger.log(Level::Warn, "cannot save search history", err);
+                                }
+                            }
+                            state.apply_found(found, query, live);
+                        }
+                        Ok(Done::Page { found, query, page }) => match found {
+                            Ok(found) => state.append_page(found, &query, page),
+                            Err(err) => {
+                                state.page_failed(&query, page);
+                                state.job_failed(&mut logger, &job, err);
+                            }
+                        },
+                        Ok(Done::List {
+                            view,
+                            refresh: false,
+                        }) => state.update_view(*view),
+                        Ok(Done::List {
+                            view,
+                            refresh: true,
+                        }) => state.apply_refreshed(*view),
+                        Ok(Done::Play { view, track }) => {
+                            let line = format!("{} is enqueued", track.name);
+                            state.update_view(*view);
+                            player_commands.send(Command::Enqueue { track }).context(
+                                PlayerCommandError {
+                                    action: Action::Search,
+                                },
+                            )?;
+                            logger.log(Level::Info, "ok", line);
+                        }
+                        Ok(Done::Downloaded { downloaded, total }) if downloaded == total => {
+                            let line = format!("{} tracks are downloaded", total);
+                            logger.log(Level::Info, "ok", line);
+                        }
+                        Ok(Done::Downloaded { downloaded, total }) => {
+                            let line = format!(
+                                "{} of {} tracks are downloaded, the failures are logged",
+                                downloaded, total
+                            );
+                            logger.log(Level::Warn, "download failed", line);
+                        }
+                        Ok(Done::Exported(progress)) if progress.failed == 0 => {
+                            let line = format!("{} is exported", progress.name);
+                            logger.log(Level::Info, "ok", line);
+                        }
+                        Ok(Done::Exported(progress)) => {
+                            let line = format!(
+                                "{} of {} tracks of {} are exported, the failures are logged",
+                                progress.done, progress.total, progress.name
+                            );
+                            logger.log(Level::Warn, "export failed", line);
+                        }
+                        Ok(Done::Liked { item, liked: true }) => {
+                            let line = format!("{} is added to the library", item.name());
+                            logger.log(Level::Info, "ok", line);
+                            // the station picks the following tracks by the likes
+                            if let meta::Likeable::Track(track) = *item {
+                                let track_id = track.track_id;
+                                player_commands.send(Command::Liked { track_id }).context(
+                                    PlayerCommandError {
+                                        action: Action::Like,
+                                    },
+                                )?;
+                            }
+                        }
+                        Ok(Done::Liked { item, liked: false }) => {
+                            let line = format!("{} is removed from the library", item.name());
+                            logger.log(Level::Info, "ok", line);
+                        }
+                        Err(err) => state.job_failed(&mut logger, &job, err),
+                    }
+
+                    *input.mode.lock().unwrap() = state.input_mode();
+                    *input.context.lock().unwrap() = key_context(state.main_view.view());
+                    state.redraw(&mut drawer, &mut logger, "job update state")?;
+                    continue;
+                }
+                Message::Tick => {
+                    state.main_view.tick_spinner();
+                    state.redraw(&mut drawer, &mut logger, "spinner update")?;
+                    continue;
+                }
+                Message::PlayerPoll => {
+                    if state.player_state.lock().unwrap().has_failures() {
+                        state.redraw(&mut drawer, &mut logger, "player failure")?;
+                    }
+                    continue;
+                }
+            };
+            let insert_buffer = state.main_view.insert_buffer().to_string();
+
+            if let Some(help) = state.main_view.help_mut() {
+                match action {
+                    Action::PointerUp => help.scroll_up(),
+                    Action::PointerDown => help.scroll_down(),
+                    Action::ShowHelp | Action::PrevView => {
+                        state.main_view.hide_help();
+                    }
+                    Action::Quit => return Ok(()),
+                    _ => continue,
+                }
+
+                state.redraw(&mut drawer, &mut logger, "help update state")?;
+                continue;
+            }
+
+            if let Some(cursor) = state.main_view.job_list_mut() {
+                match action {
+                    Action::PointerUp => *cursor = cursor.saturating_sub(1),
+                    Action::PointerDown => *cursor += 1,
+                    Action::Select | Action::Cancel => {
+                        let cursor = *cursor;
+                        if let Some(job) = state.main_view.jobs().get(cursor) {
+                            if let Some(job) = state.jobs.cancel(job.id) {
+                                logger.log(Level::Info, "ok", format!("{} is cancelled", job.name));
+                            }
+                        }
+                    }
+                    Action::ShowJobs | Action::PrevView => {
+                        state.main_view.hide_job_list();
+                    }
+                    Action::Quit => return Ok(()),
+                    _ => continue,
+                }
+
+                state.redraw(&mut drawer, &mut logger, "job list update state")?;
+                continue;
+            }
+
+            if let Some(prompt) = state.main_view.prompt_mut() {
+                let kind = prompt.kind;
+                match action {
+                    Action::Char(c) => prompt.buffer.push(c),
+                    Action::Backspace if prompt.buffer.is_empty() => {
+                        state.main_view.close_prompt();
+                        state.main_view.clear_filter();
+                    }
+                    Action::Backspace => {
+                        prompt.buffer.pop();
+                    }
+                    Action::Complete if kind == PromptKind::Command => {
+                        let candidates = cmdline::complete(&mut prompt.buffer);
+                        if candidates.len() > 1 {
+                            logger.log(Level::Info, "completions", candidates.join(" "));
+                        }
+                    }
+                    Action::PrevView => {
+                        state.main_view.close_prompt();
+                        state.main_view.clear_filter();
+                    }
+                    Action::Select if kind == PromptKind::Filter => {
+                        let pattern = state.main_view.close_prompt().unwrap().buffer;
+                        if pattern.is_empty() {
+                            state.main_view.clear_filter();
+                        }
+                    }
+                    Action::Select => {
+                        let line = state.main_view.close_prompt().unwrap().buffer;
+                        match line.parse() {
+                            Ok(cmdline::Command::Action(action)) => queued = Some(action),
+                            Ok(cmdline::Command::Search(kind, text)) => {
+                                state.prepare_search(kind, text);
+                                queued = Some(Action::Search);
+                            }
+                            Ok(cmdline::Command::Player(cmd)) => {
+                                player_commands.send(cmd).context(PlayerCommandError {
+                                    action: Action::CommandLine,
+                                })?
+                            }
+                            Ok(cmdline::Command::Save(path)) => match state.save_playlist(&path) {
+                                Ok(()) => logger.log(Level::Info, "ok", "playlist is saved"),
+                                Err(err) => logger.log(Level::Error, "cannot save playlist", err),
+                            },
+                            Ok(cmdline::Command::Bind(event, action)) => {
+                                input.binding.lock().unwrap().bind(event, action)
+                            }
+                            Ok(cmdline::Command::Set(Setting::Layout(layout))) => {
+                                input.binding.lock().unwrap().set_layout(layout)
+                            }
+                            Ok(cmdline::Command::Set(Setting::LiveSearch(enabled))) => {
+                                live_search.cancel(&mut state.jobs);
+                                live_search.enabled = enabled;
+                            }
+                            Ok(cmdline::Command::OpenCaptcha) => match state.captcha {
+                                Some(ref url) => match open_in_browser(url) {
+                                    Ok(()) => logger.log(Level::Info, "ok", "captcha is opened"),
+                                    Err(err) => {
+                                        logger.log(Level::Error, "cannot open browser", err)
+                                    }
+                                },
+                                None => logger.log(Level::Info, "ok", "no captcha is requested"),
+                            },
+                            Ok(cmdline::Command::Login(credentials)) => {
+                                if let Err(err) = auth::save(&credentials) {
+                                    logger.log(Level::Warn, "cannot save credentials", err);
+                                }
+                                let library = credentials.has_library();
+                                state.provider.login(credentials);
+                                if library {
+                                    logger.log(Level::Info, "ok", "logged in");
+                                } else {
+                                    let line = "logged in with a cookie, the library needs \
+                                                `:login token <token>`";
+                                    logger.log(Level::Warn, "login", line);
+                                }
+                            }
+                            Ok(cmdline::Command::Open(link)) => {
+                                let task = open_link(state.provider.clone(), link);
+                                state.spawn(task);
+                            }
+                            Ok(cmdline::Command::Cookie(cookie)) => {
+                                state.provider.set_cookie(cookie);
+                                state.captcha = None;
+                                logger.log(Level::Info, "ok", "session cookie is set");
+                            }
+                            Err(err) => logger.log(Level::Error, "incorrect command", err),
+                        }
+                    }
+                    Action::Quit => return Ok(()),
+                    _ => {}
+                }
+
+                if let Some(prompt) = state.main_view.prompt() {
+                    if prompt.kind == PromptKind::Filter {
+                        let pattern = prompt.buffer.clone();
+                        state.main_view.set_filter(&pattern);
+                    }
+                }
+
+                *input.mode.lock().unwrap() = state.input_mode();
+                state.redraw(&mut drawer, &mut logger, "prompt update state")?;
+                continue;
+            }
 
-        while let Some(action) = events.next().await {
             match action {
                 Action::PointerUp => state.pointer_up(),
                 Action::PointerDown => state.pointer_down(),
@@ -267,25 +1463,68 @@ impl App {
                     .send(Command::Stop)
                     .context(PlayerCommandError { action })?,
                 Action::AddAll => {
-                    if let View::TrackList(ref search) = *state.main_view {
-                        for track in search.cached_tracks.iter() {
-                            match state.provider.get_track_url(&track).await {
-                                Ok(url) => {
-                                    let track = track.clone();
-                                    player_commands
-                                        .send(Command::Enqueue { track, url })
-                                        .context(PlayerCommandError { action })?;
-                                }
-                                Err(err) => logger.log(
-                                    Level::Error,
-                                    format!("cannot get track: {:?}", track).as_str(),
-                                    err,
-                                ),
-                            }
+                    if let View::TrackList(ref list) = *state.main_view {
+                        for track in list.cached_tracks.iter().cloned() {
+                            player_commands
+                                .send(Command::Enqueue {
+                                    track: Box::new(track),
+                                })
+                                .context(PlayerCommandError { action })?;
                         }
                         logger.log(Level::Info, "ok", "all tracks are added to queue");
                     }
                 }
+                Action::Download => match state.download(config.provider.concurrency) {
+                    Some(task) => {
+                        state.spawn(task);
+                    }
+                    None => logger.log(Level::Info, "download", "nothing to download at the view"),
+                },
+                Action::Export => match state.export() {
+                    Some(task) => {
+                        state.spawn(task);
+                    }
+                    None => {
+                        let line = "select an album, an artist or a track of an album to export";
+                        logger.log(Level::Info, "export", line);
+                    }
+                },
+                Action::Logout => {
+                    state.provider.logout();
+                    match auth::remove() {
+                        Ok(()) => logger.log(Level::Info, "ok", "logged out"),
+                        Err(err) => logger.log(Level::Warn, "cannot remove credentials", err),
+                    }
+                }
+                Action::ShowLikedTracks => {
+                    state.spawn(library(state.provider.clone(), Library::Tracks, false));
+                }
+                Action::ShowLikedAlbums => {
+                    state.spawn(library(state.provider.clone(), Library::Albums, false));
+                }
+                Action::ShowFollowedArtists => {
+                    state.spawn(library(state.provider.clone(), Library::Artists, false));
+                }
+                Action::ShowMyPlaylists => {
+                    state.spawn(library(state.provider.clone(), Library::Playlists, false));
+                }
+                Action::Like | Action::Unlike => match state.like(action == Action::Like) {
+                    Some(task) => {
+                        state.spawn(task);
+                    }
+                    None => {
+                        let line = "select a track, an album or an artist to like";
+                        logger.log(Level::Info, "library", line);
+                    }
+                },
+                Action::ShowDownloads => {
+                    if let View::Downloads(_) = *state.main_view {
+                        state.restore_view();
+                    } else {
+                        let exports = state.exporter.progress();
+                        state.update_view(Downloads { exports });
+                    }
+                }
                 Action::ShowPlaylist => {
                     if let View::Playlist(_) = *state.main_view {
                         state.restore_view();
@@ -293,70 +1532,152 @@ impl App {
                         let player_state = state.player_state.lock().unwrap();
                         let tracks = player_state.playlist().cloned().collect();
                         let current = player_state.current();
+                        let station = player_state.station().map(|s| s.name.clone());
                         drop(player_state);
 
-                        state.update_view(Playlist::create(tracks, current));
+                        state.update_view(Playlist::create(tracks, current, station));
                     }
                 }
+                Action::ShowStartMenu => match *state.main_view {
+                    View::Menu(ref menu) if menu.is_start => state.restore_view(),
+                    _ => state.update_view(Menu::start()),
+                },
+                Action::StartStation => {
+                    let station = state.station();
+                    let line = format!("radio of {} is started", station.name);
+                    player_commands
+                        .send(Command::StartStation(station))
+                        .context(PlayerCommandError { action })?;
+                    logger.log(Level::Info, "ok", line);
+                }
                 Action::SwitchToAlbums => {
-                    if let Err(err) = state.switch_to_album_search().await {
-                        logger.log(Level::Error, "cannot switch to album search", err);
+                    if let Some(task) = state.switch_to_album_search() {
+                        state.spawn(task);
                     }
                 }
                 Action::SwitchToTracks => {
-                    if let Err(err) = state.switch_to_track_search().await {
-                        logger.log(Level::Error, "cannot switch to track search", err);
+                    if let Some(task) = state.switch_to_track_search() {
+                        state.spawn(task);
                     }
                 }
-                Action::SwitchToArtists => {
-                    if let Err(err) = state.switch_to_artist().await {
-                        logger.log(Level::Error, "cannot switch to artist search", err);
+                Action::SwitchToPlaylists => {
+                    if let Some(task) = state.switch_to_playlist_search() {
+                        state.spawn(task);
                     }
                 }
-                Action::Search => match state.search().await {
-                    Ok(()) => logger.log(Level::Info, "ok", "completed"),
-                    Err(err) => {
-                        logger.log(Level::Error, "search failed", err);
+                Action::SwitchToArtists => state.switch_to_artist(),
+                Action::Search => {
+                    live_search.cancel(&mut state.jobs);
+                    if let Some(link) = Link::parse(state.main_view.insert_buffer()) {
+                        state.main_view.insert_buffer_mut().clear();
+                        let task = open_link(state.provider.clone(), link);
+                        state.spawn(task);
+                    } else if let Some(task) = state.search(false) {
+                        state.spawn(task);
                     }
-                },
-                Action::Select => match state.select().await {
-                    Ok(cmd) => {
-                        if let Some(cmd) = cmd {
-                            player_commands
-                                .send(cmd)
-                                .context(PlayerCommandError { action })?;
+                }
+                Action::Select => {
+                    if let Some(track) = state.selected_track().cloned() {
+                        player_commands
+                            .send(Command::Enqueue {
+                                track: Box::new(track),
+                            })
+                            .context(PlayerCommandError { action })?;
+                    } else if let Some(entry) = state.selected_entry() {
+                        match entry {
+                            MenuEntry::Genre(genre) => state.update_view(Menu::genre(genre)),
+                            MenuEntry::GenreRadio(genre) => {
+                                let station = meta::Station::of_genre(&genre.id);
+                                let line = format!("radio of {} is started", genre.name);
+                                player_commands
+                                    .send(Command::StartStation(station))
+                                    .context(PlayerCommandError { action })?;
+                                logger.log(Level::Info, "ok", line);
+                            }
+                            MenuEntry::List(destination) => {
+                                let task = discover(state.provider.clone(), destination, false);
+                                state.spawn(task);
+                            }
                         }
-                        logger.log(Level::Info, "ok", "completed");
+                    } else if let Some(view) = state.selected_section() {
+                        state.update_view(view);
+                    } else if let Some(task) = state.select() {
+                        state.spawn(task);
                     }
-                    Err(err) => logger.log(Level::Error, "cannot perform action", err),
-                },
+                }
                 Action::SwitchView => match state.main_view.view().clone() {
                     View::AlbumSearch(_) => state.update_view(TrackList::default()),
                     View::TrackList(_) => state.update_view(ArtistSearch::default()),
-                    View::ArtistSearch(_) => state.update_view(AlbumSearch::default()),
+                    View::ArtistSearch(_) => state.update_view(PlaylistSearch::default()),
+                    View::PlaylistSearch(_) => state.update_view(AlbumSearch::default()),
                     _ => continue,
                 },
                 Action::PrevView => {
-                    if let Some(view) = state.prev_view.take() {
-                        state.main_view.replace_view(view);
+                    if state.main_view.clear_filter().is_none() {
+                        state.restore_view();
                     }
                 }
                 Action::Char(c) => state.push_char(c),
                 Action::Backspace => state.backspace(),
-                Action::Refresh => continue,
+                Action::CursorLeft => state.main_view.insert_buffer_mut().left(),
+                Action::CursorRight => state.main_view.insert_buffer_mut().right(),
+                Action::LineStart => state.main_view.insert_buffer_mut().home(),
+                Action::LineEnd => state.main_view.insert_buffer_mut().end(),
+                Action::WordLeft => state.main_view.insert_buffer_mut().word_left(),
+                Action::WordRight => state.main_view.insert_buffer_mut().word_right(),
+                Action::DeleteWord => {
+                    state.reset_history();
+                    state.main_view.insert_buffer_mut().delete_word();
+                }
+                Action::DeleteLine => {
+                    state.reset_history();
+                    state.main_view.insert_buffer_mut().delete_to_start();
+                }
+                Action::HistoryPrev => state.history_prev(),
+                Action::HistoryNext => state.history_next(),
+                Action::Refresh => match state.refresh() {
+                    Some(task) => {
+                        state.spawn(task);
+                    }
+                    None => logger.log(Level::Info, "refresh", "nothing to refresh at the view"),
+                },
+                Action::ShowHelp => {
+                    let help = create_help(&input.binding.lock().unwrap());
+                    state.main_view.show_help(help);
+                }
+                Action::CommandLine => state.main_view.open_prompt(PromptKind::Command),
+                Action::Filter => {
+                    if state.main_view.cursor().is_some() {
+                        state.main_view.open_prompt(PromptKind::Filter);
+                    }
+                }
+                Action::NextMatch => state.jump_match(true),
+                Action::PrevMatch => state.jump_match(false),
+                Action::Complete => continue,
+                Action::Cancel => match state.jobs.cancel_last() {
+                    Some(job) => {
+                        logger.log(Level::Info, "ok", format!("{} is cancelled", job.name))
+                    }
+                    None => continue,
+                },
+                Action::ShowJobs => state.main_view.show_job_list(),
             }
 
-            *current_context.lock().unwrap() = match state.main_view.view() {
-                View::AlbumSearch(_) | View::ArtistSearch(_) => KeyContext::search(),
-                View::TrackList(_) => KeyContext::search() | KeyContext::tracklist(),
-                View::Playlist(_) => KeyContext::playlist(),
-            };
+            if live_search.enabled && insert_buffer.as_str() != &**state.main_view.insert_buffer() {
+                if state.search_query().is_some() {
+                    live_search.schedule(&mut state.jobs);
+                } else {
+                    live_search.cancel(&mut state.jobs);
+                }
+            }
+
+            if let Some(task) = state.load_page() {
+                state.spawn(task);
+            }
 
-            drawer
-                .redraw(&state.main_view, logger.log_lines())
-                .context(Drawer {
-                    case: "loop update state",
-                })?;
+            *input.mode.lock().unwrap() = state.input_mode();
+            *input.context.lock().unwrap() = key_context(state.main_view.view());
+            state.redraw(&mut drawer, &mut logger, "loop update state")?;
         }
         Ok(())
     }
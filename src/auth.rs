@@ -0,0 +1,84 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+// This file contains credentials of the Yandex account, stored at the config directory
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Credentials {
+    /// OAuth token of the account
+    Token(String),
+    /// Cookie of a browser session, e.g. `Session_id=...`
+    Cookie(String),
+}
+
+impl Credentials {
+    /// Credentials of the kind (`token` or `cookie`)
+    pub fn parse(kind: &str, value: &str) -> Option<Self> {
+        let value = value.trim().to_string();
+        if value.is_empty() {
+            return None;
+        }
+        match kind {
+            "token" => Some(Credentials::Token(value)),
+            "cookie" => Some(Credentials::Cookie(value)),
+            _ => None,
+        }
+    }
+
+    /// Whether the library is available, its api accepts the tokens only
+    pub fn has_library(&self) -> bool {
+        matches!(self, Credentials::Token(_))
+    }
+}
+
+impl fmt::Display for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credentials::Token(token) => write!(f, "token {}", token),
+            Credentials::Cookie(cookie) => write!(f, "cookie {}", cookie),
+        }
+    }
+}
+
+fn credentials_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("rum-player").join("credentials"))
+}
+
+/// Credentials saved by the last login, if any
+pub fn load() -> Option<Credentials> {
+    let content = fs::read_to_string(credentials_path()?).ok()?;
+    let (kind, value) = content.trim().split_once(' ')?;
+    Credentials::parse(kind, value)
+}
+
+/// Saves the credentials, so that only the user might read them
+pub fn save(credentials: &Credentials) -> io::Result<()> {
+    let path = credentials_path()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        options.mode(0o600);
+        // the mode is applied to the newly created files only
+        if path.exists() {
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    writeln!(options.open(&path)?, "{}", credentials)
+}
+
+pub fn remove() -> io::Result<()> {
+    match credentials_path().map(fs::remove_file) {
+        Some(Err(err)) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
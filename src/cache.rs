@@ -0,0 +1,153 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use crate::config::CacheConfig;
+
+// This file contains a disk cache of the provider pages
+
+/// Kind of the cached page, each one has its own time to live
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheKind {
+    Search,
+    ArtistAlbums,
+    ArtistTracks,
+    AlbumTracks,
+    Landing,
+}
+
+/// Response bodies stored by their urls, the oldest ones are removed when the size limit is hit
+#[derive(Debug)]
+pub struct HttpCache {
+    dir: Option<PathBuf>,
+    config: CacheConfig,
+}
+
+impl HttpCache {
+    /// Cache at `$XDG_CACHE_HOME/rum-player/http`
+    pub fn new(config: &CacheConfig) -> Self {
+        let dir = dirs::cache_dir()
+            .filter(|_| config.enabled)
+            .map(|dir| dir.join("rum-player").join("http"));
+        Self::at(dir, config)
+    }
+
+    fn at(dir: Option<PathBuf>, config: &CacheConfig) -> Self {
+        Self {
+            dir,
+            config: config.clone(),
+        }
+    }
+
+    fn path(&self, url: &str) -> Option<PathBuf> {
+        let mut hasher = DefaultHasher::new();
+        url.hash(&mut hasher);
+        Some(self.dir.as_ref()?.join(format!("{:016x}", hasher.finish())))
+    }
+
+    fn ttl(&self, kind: CacheKind) -> Duration {
+        match kind {
+            CacheKind::Search => self.config.search,
+            CacheKind::ArtistAlbums => self.config.artist_albums,
+            CacheKind::ArtistTracks => self.config.artist_tracks,
+            CacheKind::AlbumTracks => self.config.album_tracks,
+            CacheKind::Landing => self.config.landing,
+        }
+    }
+
+    /// Body of the url, unless it is missing or expired
+    pub fn get(&self, url: &str, kind: CacheKind) -> Option<String> {
+        let path = self.path(url)?;
+        let age = fs::metadata(&path)
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())?;
+        if age > self.ttl(kind) {
+            return None;
+        }
+
+        // the first line is the url, so that hash collisions are not confused
+        let content = fs::read_to_string(&path).ok()?;
+        let (cached_url, body) = content.split_once('\n')?;
+        if cached_url == url {
+            Some(body.to_string())
+        } else {
+            None
+        }
+    }
+
+    pub fn put(&self, url: &str, body: &str) {
+        let path = match self.path(url) {
+            Some(path) => path,
+            None => return,
+        };
+        let stored = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&path, format!("{}\n{}", url, body)))
+            .and_then(|()| self.trim());
+        if let Err(err) = stored {
+            log::warn!("cannot cache {}: {}", url, err);
+        }
+    }
+
+    /// Removes the oldest entries, until the cache fits the size limit
+    fn trim(&self) -> io::Result<()> {
+        let dir = match self.dir {
+            Some(ref dir) => dir,
+            None => return Ok(()),
+        };
+
+        let mut entries = vec![];
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            entries.push((meta.modified()?, meta.len(), entry.path()));
+        }
+
+        let mut size: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        entries.sort();
+        for (_, len, path) in entries {
+            if size <= self.config.max_size {
+                break;
+            }
+            fs::remove_file(path)?;
+            size -= len;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache() {
+        let dir = std::env::temp_dir().join(format!("rum-player-cache-{}", std::process::id()));
+        let config = CacheConfig {
+            search: Duration::from_secs(0),
+            max_size: 40,
+            ..CacheConfig::default()
+        };
+        let cache = HttpCache::at(Some(dir.clone()), &config);
+
+        cache.put("https://a", "album page");
+        assert_eq!(
+            cache.get("https://a", CacheKind::AlbumTracks).as_deref(),
+            Some("album page")
+        );
+        assert_eq!(cache.get("https://a", CacheKind::Search), None);
+        assert_eq!(cache.get("https://b", CacheKind::AlbumTracks), None);
+
+        // the size limit is hit, so at most one page is left
+        cache.put("https://b", "another album page");
+        cache.put("https://c", "the third album page");
+        let left = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(left, 1);
+    }
+}
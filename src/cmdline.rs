@@ -0,0 +1,408 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use snafu::ResultExt;
+use termion::event::Event;
+
+use crate::auth::Credentials;
+use crate::config::{self, UnknownEvent};
+use crate::key::{Action, Context, ContextedAction, Layout};
+use crate::meta::Station;
+use crate::player;
+use crate::providers::Link;
+use crate::view::SearchKind;
+
+// This file contains ex-style commands, typed after `:`
+
+#[derive(Debug)]
+pub enum Setting {
+    Layout(Layout),
+    LiveSearch(bool),
+}
+
+#[derive(Debug)]
+pub enum Command {
+    Action(Action),
+    Search(SearchKind, String),
+    Player(player::Command),
+    Save(PathBuf),
+    Bind(Event, ContextedAction),
+    Set(Setting),
+    /// Opens the captcha of the last failed request in a browser
+    OpenCaptcha,
+    Cookie(String),
+    Login(Credentials),
+    /// Jumps to the item of a yandex music link
+    Open(Link),
+}
+
+#[derive(Debug, snafu::Snafu)]
+pub enum Error {
+    #[snafu(display("unknown command: {}", name))]
+    UnknownCommand { name: String },
+    #[snafu(display("missing argument: {}", name))]
+    MissingArgument { name: &'static str },
+    #[snafu(display("incorrect {}: {}", name, value))]
+    IncorrectArgument { name: &'static str, value: String },
+    #[snafu(display("incorrect key {}: {}", value, source))]
+    IncorrectKey { value: String, source: UnknownEvent },
+}
+
+const COMMANDS: &[&str] = &[
+    "search", "seek", "vol", "save", "bind", "set", "captcha", "cookie", "login", "radio", "open",
+];
+const STATIONS: &[&str] = &["wave", "genre"];
+const CREDENTIALS: &[&str] = &["token", "cookie"];
+const SEARCH_KINDS: &[&str] = &["album", "track", "artist", "playlist"];
+const CONTEXTS: &[&str] = &["search", "tracklist", "playlist"];
+const SETTINGS: &[&str] = &["layout", "live_search"];
+const LAYOUTS: &[&str] = &["jcuken", "none"];
+const SWITCHES: &[&str] = &["on", "off"];
+
+/// Parses either seconds (`80`) or minutes with seconds (`1:20`)
+fn parse_time(s: &str) -> Option<i64> {
+    let mut parts = s.rsplitn(2, ':');
+    let secs: i64 = parts.next()?.parse().ok()?;
+    let mins: i64 = parts.next().map(str::parse).transpose().ok()?.unwrap_or(0);
+    Some(mins * 60 + secs)
+}
+
+fn parse_seek(arg: &str) -> Option<player::Command> {
+    if let Some(secs) = arg.strip_prefix('+') {
+        parse_time(secs).map(player::Command::Seek)
+    } else if let Some(secs) = arg.strip_prefix('-') {
+        parse_time(secs).map(|secs| player::Command::Seek(-secs))
+    } else {
+        parse_time(arg).map(player::Command::SeekTo)
+    }
+}
+
+impl FromStr for Command {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, rest) = match s.split_once(' ') {
+            Some((name, rest)) => (name, rest.trim()),
+            None => (s, ""),
+        };
+        let mut args = rest.split_whitespace();
+        let mut arg = |name| args.next().ok_or(Error::MissingArgument { name });
+
+        let command = match name {
+            "search" => {
+                let kind = match arg("kind")? {
+                    "album" => SearchKind::Album,
+                    "track" => SearchKind::Track,
+                    "artist" => SearchKind::Artist,
+                    "playlist" => SearchKind::Playlist,
+                    value => {
+                        return Err(Error::IncorrectArgument {
+                            name: "search kind",
+                            value: value.to_string(),
+                        })
+                    }
+                };
+                let text = rest.split_once(' ').map(|(_, text)| text.trim());
+                let text = text.unwrap_or("");
+                if text.is_empty() {
+                    return Err(Error::MissingArgument { name: "query" });
+                }
+                Command::Search(kind, text.to_string())
+            }
+            "seek" => {
+                let value = arg("time")?;
+                let cmd = parse_seek(value).ok_or_else(|| Error::IncorrectArgument {
+                    name: "time",
+                    value: value.to_string(),
+                })?;
+                Command::Player(cmd)
+            }
+            "vol" => {
+                let value = arg("volume")?;
+                let volume = value
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|volume| *volume <= 100)
+                    .ok_or_else(|| Error::IncorrectArgument {
+                        name: "volume",
+                        value: value.to_string(),
+                    })?;
+                Command::Player(player::Command::Volume(volume))
+            }
+            "save" => {
+                if rest.is_empty() {
+                    return Err(Error::MissingArgument { name: "file" });
+                }
+                Command::Save(PathBuf::from(rest))
+            }
+            "bind" => {
+                let key = arg("key")?;
+                let event = config::parse_event(key).context(IncorrectKey { value: key })?;
+                let value = arg("action")?;
+                let action = Action::by_name(value).ok_or_else(|| Error::IncorrectArgument {
+                    name: "action",
+                    value: value.to_string(),
+                })?;
+                let context = match args.next() {
+                    Some(value) => {
+                        Context::by_name(value).ok_or_else(|| Error::IncorrectArgument {
+                            name: "context",
+                            value: value.to_string(),
+                        })?
+                    }
+                    None => Context::all(),
+                };
+                Command::Bind(event, ContextedAction { context, action })
+            }
+            "captcha" => Command::OpenCaptcha,
+            "cookie" => {
+                if rest.is_empty() {
+                    return Err(Error::MissingArgument { name: "cookie" });
+                }
+                Command::Cookie(rest.to_string())
+            }
+            "login" => {
+                let kind = arg("credentials kind")?;
+                let value = rest.split_once(' ').map_or("", |(_, value)| value);
+                if value.trim().is_empty() {
+                    return Err(Error::MissingArgument {
+                        name: "credentials",
+                    });
+                }
+                let credentials =
+                    Credentials::parse(kind, value).ok_or_else(|| Error::IncorrectArgument {
+                        name: "credentials kind",
+                        value: kind.to_string(),
+                    })?;
+                Command::Login(credentials)
+            }
+            "radio" => {
+                let station = match arg("station")? {
+                    "wave" => Station::my_wave(),
+                    "genre" => Station::of_genre(arg("genre")?),
+                    value => {
+                        return Err(Error::IncorrectArgument {
+                            name: "station",
+                            value: value.to_string(),
+                        })
+                    }
+                };
+                Command::Player(player::Command::StartStation(station))
+            }
+            "open" => {
+                let value = arg("link")?;
+                let link = Link::parse(value).ok_or_else(|| Error::IncorrectArgument {
+                    name: "link",
+                    value: value.to_string(),
+                })?;
+                Command::Open(link)
+            }
+            "set" => match arg("option")? {
+                "layout" => {
+                    let value = arg("layout")?;
+                    let layout = if value == "none" {
+                        Layout::default()
+                    } else {
+                        Layout::builtin(value).ok_or_else(|| Error::IncorrectArgument {
+                            name: "layout",
+                            value: value.to_string(),
+                        })?
+                    };
+                    Command::Set(Setting::Layout(layout))
+                }
+                "live_search" => {
+                    let enabled = match arg("switch")? {
+                        "on" => true,
+                        "off" => false,
+                        value => {
+                            return Err(Error::IncorrectArgument {
+                                name: "switch",
+                                value: value.to_string(),
+                            })
+                        }
+                    };
+                    Command::Set(Setting::LiveSearch(enabled))
+                }
+                value => {
+                    return Err(Error::IncorrectArgument {
+                        name: "option",
+                        value: value.to_string(),
+                    })
+                }
+            },
+            name => {
+                Action::by_name(name)
+                    .map(Command::Action)
+                    .ok_or_else(|| Error::UnknownCommand {
+                        name: name.to_string(),
+                    })?
+            }
+        };
+        Ok(command)
+    }
+}
+
+fn path_candidates(prefix: &str) -> Vec<String> {
+    let (dir, file) = match prefix.rfind('/') {
+        Some(pos) => prefix.split_at(pos + 1),
+        None => ("", prefix),
+    };
+    let entries = match std::fs::read_dir(if dir.is_empty() {
+        Path::new(".")
+    } else {
+        Path::new(dir)
+    }) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+
+    entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(file) {
+                return None;
+            }
+            let suffix = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir, name, suffix))
+        })
+        .collect()
+}
+
+/// Candidates for the last word of the command line
+fn candidates(words: &[&str], last: &str) -> Vec<String> {
+    let names = |names: &[&str]| -> Vec<String> {
+        names
+            .iter()
+            .filter(|name| name.starts_with(last))
+            .map(|name| name.to_string())
+            .collect()
+    };
+    let actions = || -> Vec<String> {
+        Action::bindable()
+            .map(Action::name)
+            .filter(|name| name.starts_with(last))
+            .map(str::to_string)
+            .collect()
+    };
+
+    match words {
+        [] => {
+            let mut names = names(COMMANDS);
+            names.extend(actions());
+            names
+        }
+        ["search"] => names(SEARCH_KINDS),
+        ["save", ..] => path_candidates(last),
+        ["bind", _] => actions(),
+        ["bind", _, _] => names(CONTEXTS),
+        ["set"] => names(SETTINGS),
+        ["set", "layout"] => names(LAYOUTS),
+        ["set", "live_search"] => names(SWITCHES),
+        ["login"] => names(CREDENTIALS),
+        ["radio"] => names(STATIONS),
+        _ => vec![],
+    }
+}
+
+fn common_prefix(candidates: &[String]) -> &str {
+    let first = match candidates.first() {
+        Some(first) => first.as_str(),
+        None => return "",
+    };
+    let len = candidates
+        .iter()
+        .skip(1)
+        .fold(first.len(), |len, candidate| {
+            first[..len]
+                .char_indices()
+                .zip(candidate.chars())
+                .find(|((_, a), b)| a != b)
+                .map(|((i, _), _)| i)
+                .unwrap_or_else(|| len.min(candidate.len()))
+        });
+    &first[..len]
+}
+
+/// Completes the last word of the line in place, returns all possible candidates
+pub fn complete(line: &mut String) -> Vec<String> {
+    let last_start = line.rfind(' ').map(|pos| pos + 1).unwrap_or(0);
+    let words = line[..last_start].split_whitespace().collect::<Vec<_>>();
+    let candidates = candidates(&words, &line[last_start..]);
+
+    let completed = match candidates.as_slice() {
+        [single] if !single.ends_with('/') => format!("{} ", single),
+        candidates => common_prefix(candidates).to_string(),
+    };
+    if completed.len() > line.len() - last_start {
+        line.truncate(last_start);
+        line.push_str(&completed);
+    }
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        match "search album the wall".parse() {
+            Ok(Command::Search(SearchKind::Album, text)) => assert_eq!(text, "the wall"),
+            cmd => panic!("unexpected {:?}", cmd),
+        }
+        match "seek 1:20".parse() {
+            Ok(Command::Player(player::Command::SeekTo(80))) => {}
+            cmd => panic!("unexpected {:?}", cmd),
+        }
+        match "seek -5".parse() {
+            Ok(Command::Player(player::Command::Seek(-5))) => {}
+            cmd => panic!("unexpected {:?}", cmd),
+        }
+        match "NextTrack".parse() {
+            Ok(Command::Action(Action::NextTrack)) => {}
+            cmd => panic!("unexpected {:?}", cmd),
+        }
+        match "cookie Session_id=3:abc; yandexuid=42".parse() {
+            Ok(Command::Cookie(cookie)) => assert_eq!(cookie, "Session_id=3:abc; yandexuid=42"),
+            cmd => panic!("unexpected {:?}", cmd),
+        }
+        match "login token AQAAAA".parse() {
+            Ok(Command::Login(Credentials::Token(token))) => assert_eq!(token, "AQAAAA"),
+            cmd => panic!("unexpected {:?}", cmd),
+        }
+        match "radio genre rock".parse() {
+            Ok(Command::Player(player::Command::StartStation(station))) => {
+                assert_eq!(station.id, "genre:rock")
+            }
+            cmd => panic!("unexpected {:?}", cmd),
+        }
+        match "open https://music.yandex.ru/album/4766".parse() {
+            Ok(Command::Open(Link::Album(album))) => assert_eq!(album.id, 4766),
+            cmd => panic!("unexpected {:?}", cmd),
+        }
+        assert!("open https://example.com/album/4766"
+            .parse::<Command>()
+            .is_err());
+        assert!("login password qwerty".parse::<Command>().is_err());
+        assert!("vol 150".parse::<Command>().is_err());
+        assert!("unknown".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn test_completion() {
+        let mut line = "se".to_string();
+        let candidates = complete(&mut line);
+        assert_eq!(candidates, vec!["search", "seek", "set"]);
+        assert_eq!(line, "se");
+
+        let mut line = "search al".to_string();
+        complete(&mut line);
+        assert_eq!(line, "search album ");
+
+        let mut line = "bind + NextT".to_string();
+        complete(&mut line);
+        assert_eq!(line, "bind + NextTrack ");
+    }
+}
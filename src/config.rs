@@ -1,12 +1,16 @@
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::fmt;
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 
 use snafu::ResultExt;
 use termion::event::{Event as InnerEvent, Key};
 
 use crate::key::BindingConfig;
-use crate::key::{Action, Context, ContextedAction};
+use crate::key::{Action, Context, ContextedAction, Layout};
+use crate::providers::Backend;
 
 struct Event(InnerEvent);
 
@@ -78,6 +82,10 @@ impl FromStr for Event {
     }
 }
 
+pub fn parse_event(s: &str) -> Result<InnerEvent, UnknownEvent> {
+    s.parse::<Event>().map(|event| event.0)
+}
+
 #[derive(Debug, snafu::Snafu)]
 pub enum Error {
     #[snafu(display("incorrect toml config: {}", source))]
@@ -89,18 +97,134 @@ pub enum Error {
     },
     #[snafu(display("incorrect event value: {}", value))]
     IncorrectEvent { value: String, source: UnknownEvent },
+    #[snafu(display("unknown keyboard layout: {}", name))]
+    UnknownLayout { name: String },
+    #[snafu(display("unknown provider backend: {}, use json or html", name))]
+    UnknownBackend { name: String },
+    #[snafu(display("incorrect layout char: {}", value))]
+    IncorrectLayoutChar { value: String },
+    #[snafu(display("value of {} is out of range: {}", key, value))]
+    NumberOutOfRange { key: String, value: i64 },
     #[snafu(display("unsupported config key {}", key))]
     UnsupportedKey { key: String },
     #[snafu(display("unsupported toml item"))]
     UnsupportedTomlItem,
 }
 
+#[derive(Debug)]
+pub struct SearchConfig {
+    /// Search while typing, after a pause
+    pub live: bool,
+    pub delay: Duration,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            live: false,
+            delay: Duration::from_millis(400),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ProviderConfig {
+    /// Maximum amount of simultaneously resolved stream urls
+    pub concurrency: usize,
+    pub timeout: Duration,
+    /// Amount of retries of a failed request, if it might succeed later
+    pub retries: u32,
+    /// Delay before the first retry, doubled for each next one
+    pub backoff: Duration,
+    pub backend: Backend,
+}
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            timeout: Duration::from_secs(5),
+            retries: 2,
+            backoff: Duration::from_millis(500),
+            backend: Backend::Json,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    /// Maximum size of the cached pages in bytes
+    pub max_size: u64,
+    pub search: Duration,
+    pub artist_albums: Duration,
+    pub artist_tracks: Duration,
+    pub album_tracks: Duration,
+    /// Charts, new releases and genre pages
+    pub landing: Duration,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        const HOUR: u64 = 60 * 60;
+        Self {
+            enabled: true,
+            max_size: 50 * 1024 * 1024,
+            search: Duration::from_secs(HOUR),
+            artist_albums: Duration::from_secs(24 * HOUR),
+            artist_tracks: Duration::from_secs(24 * HOUR),
+            album_tracks: Duration::from_secs(7 * 24 * HOUR),
+            landing: Duration::from_secs(6 * HOUR),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct OfflineConfig {
+    /// Maximum size of the downloaded tracks in bytes
+    pub max_size: u64,
+}
+
+impl Default for OfflineConfig {
+    fn default() -> Self {
+        Self {
+            max_size: 2 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ExportConfig {
+    /// Root of the exported `Artist/Year - Album/NN - Title.mp3` files
+    pub dir: PathBuf,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        let dir = dirs::audio_dir()
+            .or_else(|| dirs::home_dir().map(|home| home.join("Music")))
+            .unwrap_or_else(|| PathBuf::from("Music"));
+        Self { dir }
+    }
+}
+
 #[derive(Default, Debug)]
 pub struct Config {
     pub binding: BindingConfig,
+    pub search: SearchConfig,
+    pub provider: ProviderConfig,
+    pub cache: CacheConfig,
+    pub offline: OfflineConfig,
+    pub export: ExportConfig,
 }
 
 const HOTKEY_TABLE: &str = "hotkey";
+const LAYOUT_KEY: &str = "layout";
+const SEARCH_TABLE: &str = "search";
+const PROVIDER_TABLE: &str = "provider";
+const CACHE_TABLE: &str = "cache";
+const OFFLINE_TABLE: &str = "offline";
+const EXPORT_TABLE: &str = "export";
 
 macro_rules! try_toml {
     ($val:expr; $t:ident) => {{
@@ -112,49 +236,76 @@ macro_rules! try_toml {
     }};
 }
 
+/// Converts the toml integer, the negative and too large ones are rejected instead of wrapping
+fn non_negative<T: TryFrom<i64>>(key: &str, value: i64) -> Result<T, Error> {
+    T::try_from(value).map_err(|_| Error::NumberOutOfRange {
+        key: key.to_string(),
+        value,
+    })
+}
+
+/// Size in bytes of the toml integer in megabytes
+fn megabytes(key: &str, value: i64) -> Result<u64, Error> {
+    non_negative::<u64>(key, value)?
+        .checked_mul(1024 * 1024)
+        .ok_or_else(|| Error::NumberOutOfRange {
+            key: key.to_string(),
+            value,
+        })
+}
+
 impl FromStr for Config {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut config = Config {
-            binding: BindingConfig::default(),
-        };
+        let mut binding = BindingConfig::default();
+        let mut layout = None;
+        let mut search = SearchConfig::default();
+        let mut provider = ProviderConfig::default();
+        let mut cache = CacheConfig::default();
+        let mut offline = OfflineConfig::default();
+        let mut export = ExportConfig::default();
 
         for (key, value) in try_toml!(s.parse().context(IncorrectToml {})?; Table).into_iter() {
             match key.as_str() {
                 HOTKEY_TABLE => {
                     let value = try_toml!(value; Table);
-                    config.binding = parse_binding_config(value)?;
+                    binding = parse_binding_config(value)?;
                 }
+                LAYOUT_KEY => layout = Some(parse_layout(value)?),
+                SEARCH_TABLE => search = parse_search_config(try_toml!(value; Table))?,
+                PROVIDER_TABLE => provider = parse_provider_config(try_toml!(value; Table))?,
+                CACHE_TABLE => cache = parse_cache_config(try_toml!(value; Table))?,
+                OFFLINE_TABLE => offline = parse_offline_config(try_toml!(value; Table))?,
+                EXPORT_TABLE => export = parse_export_config(try_toml!(value; Table))?,
                 _ => return Err(Error::UnsupportedKey { key }),
             }
         }
 
-        Ok(config)
+        if let Some(layout) = layout {
+            binding.set_layout(layout);
+        }
+
+        Ok(Config {
+            binding,
+            search,
+            provider,
+            cache,
+            offline,
+            export,
+        })
     }
 }
 
 fn parse_binding_config(table: toml::value::Table) -> Result<BindingConfig, Error> {
-    const SEARCH_TABLE: &str = "search";
-    const TRACKLIST_TABLE: &str = "tracklist";
-    const PLAYLIST_TABLE: &str = "playlist";
-
     let mut event_actions: HashMap<_, Vec<_>> = HashMap::new();
     for (key, value) in table.into_iter() {
-        let (context, map): (Context, toml::map::Map<_, _>) = match key.as_str() {
-            SEARCH_TABLE => {
-                let map = try_toml!(value; Table);
-                (Context::search(), map)
-            }
-            TRACKLIST_TABLE => {
-                let map = try_toml!(value; Table);
-                (Context::tracklist(), map)
-            }
-            PLAYLIST_TABLE => {
+        let (context, map): (Context, toml::map::Map<_, _>) = match Context::by_name(&key) {
+            Some(context) => {
                 let map = try_toml!(value; Table);
-                (Context::playlist(), map)
+                (context, map)
             }
-            _ => {
+            None => {
                 let mut map = toml::map::Map::new();
                 map.insert(key, value);
                 (Context::all(), map)
@@ -168,11 +319,7 @@ fn parse_binding_config(table: toml::value::Table) -> Result<BindingConfig, Erro
             let action = ContextedAction { action, context };
 
             let value: String = try_toml!(value; String);
-            let event = value
-                .as_str()
-                .parse::<Event>()
-                .context(IncorrectEvent { value })?
-                .0;
+            let event = parse_event(&value).context(IncorrectEvent { value })?;
 
             event_actions.entry(event).or_default().push(action);
         }
@@ -181,6 +328,121 @@ fn parse_binding_config(table: toml::value::Table) -> Result<BindingConfig, Erro
     Ok(event_actions.into())
 }
 
+fn parse_search_config(table: toml::value::Table) -> Result<SearchConfig, Error> {
+    let mut config = SearchConfig::default();
+    for (key, value) in table {
+        match key.as_str() {
+            "live" => config.live = try_toml!(value; Boolean),
+            "delay" => {
+                let millis = non_negative(&key, try_toml!(value; Integer))?;
+                config.delay = Duration::from_millis(millis);
+            }
+            _ => return Err(Error::UnsupportedKey { key }),
+        }
+    }
+    Ok(config)
+}
+
+fn parse_provider_config(table: toml::value::Table) -> Result<ProviderConfig, Error> {
+    let mut config = ProviderConfig::default();
+    for (key, value) in table {
+        match key.as_str() {
+            "concurrency" => {
+                let value = try_toml!(value; Integer);
+                config.concurrency = non_negative(&key, value)?;
+                if config.concurrency == 0 {
+                    return Err(Error::NumberOutOfRange { key, value });
+                }
+            }
+            "timeout" => {
+                let millis = non_negative(&key, try_toml!(value; Integer))?;
+                config.timeout = Duration::from_millis(millis);
+            }
+            "retries" => config.retries = non_negative(&key, try_toml!(value; Integer))?,
+            "backoff" => {
+                let millis = non_negative(&key, try_toml!(value; Integer))?;
+                config.backoff = Duration::from_millis(millis);
+            }
+            "backend" => {
+                let name = try_toml!(value; String);
+                config.backend = name.parse().map_err(|_| Error::UnknownBackend { name })?;
+            }
+            _ => return Err(Error::UnsupportedKey { key }),
+        }
+    }
+    Ok(config)
+}
+
+/// Cache size is set in megabytes and times to live in seconds
+fn parse_cache_config(table: toml::value::Table) -> Result<CacheConfig, Error> {
+    let mut config = CacheConfig::default();
+    for (key, value) in table {
+        let seconds = |value| -> Result<_, Error> {
+            Ok(Duration::from_secs(non_negative(
+                &key,
+                try_toml!(value; Integer),
+            )?))
+        };
+        match key.as_str() {
+            "enabled" => config.enabled = try_toml!(value; Boolean),
+            "size" => config.max_size = megabytes(&key, try_toml!(value; Integer))?,
+            "search" => config.search = seconds(value)?,
+            "artist_albums" => config.artist_albums = seconds(value)?,
+            "artist_tracks" => config.artist_tracks = seconds(value)?,
+            "album_tracks" => config.album_tracks = seconds(value)?,
+            "landing" => config.landing = seconds(value)?,
+            _ => return Err(Error::UnsupportedKey { key }),
+        }
+    }
+    Ok(config)
+}
+
+/// Size of the downloaded tracks is set in megabytes
+fn parse_offline_config(table: toml::value::Table) -> Result<OfflineConfig, Error> {
+    let mut config = OfflineConfig::default();
+    for (key, value) in table {
+        match key.as_str() {
+            "size" => config.max_size = megabytes(&key, try_toml!(value; Integer))?,
+            _ => return Err(Error::UnsupportedKey { key }),
+        }
+    }
+    Ok(config)
+}
+
+fn parse_export_config(table: toml::value::Table) -> Result<ExportConfig, Error> {
+    let mut config = ExportConfig::default();
+    for (key, value) in table {
+        match key.as_str() {
+            "dir" => config.dir = PathBuf::from(try_toml!(value; String)),
+            _ => return Err(Error::UnsupportedKey { key }),
+        }
+    }
+    Ok(config)
+}
+
+/// Layout is either a name of a builtin one (e.g. `layout = "jcuken"`)
+/// or a table of custom char mappings (e.g. `"ф" = "a"`).
+fn parse_layout(value: toml::Value) -> Result<Layout, Error> {
+    if let toml::Value::String(name) = value {
+        return Layout::builtin(&name).ok_or(Error::UnknownLayout { name });
+    }
+
+    fn single_char(value: String) -> Result<char, Error> {
+        let mut chars = value.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(Error::IncorrectLayoutChar { value }),
+        }
+    }
+
+    let mut layout = Layout::default();
+    for (from, to) in try_toml!(value; Table) {
+        let to = try_toml!(to; String);
+        layout.insert(single_char(from)?, single_char(to)?);
+    }
+    Ok(layout)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -208,4 +470,74 @@ mod tests {
         let config = sample_toml.parse::<Config>().unwrap();
         println!("{:?}", config);
     }
+
+    #[test]
+    fn parse_layout_config() {
+        assert!(r#"layout = "jcuken""#.parse::<Config>().is_ok());
+        assert!(r#"layout = "dvorak""#.parse::<Config>().is_err());
+
+        let custom = r#"
+[layout]
+"ф" = "a"
+"#;
+        assert!(custom.parse::<Config>().is_ok());
+    }
+
+    #[test]
+    fn parse_search_toml() {
+        let config = "[search]\nlive = true\ndelay = 250"
+            .parse::<Config>()
+            .unwrap();
+        assert!(config.search.live);
+        assert_eq!(config.search.delay, Duration::from_millis(250));
+        assert!("[search]\nlive = 1".parse::<Config>().is_err());
+        assert!("[search]\ndelay = -1".parse::<Config>().is_err());
+    }
+
+    #[test]
+    fn parse_provider_toml() {
+        let config = "[provider]\nconcurrency = 2".parse::<Config>().unwrap();
+        assert_eq!(config.provider.concurrency, 2);
+        assert_eq!(Config::default().provider.concurrency, 4);
+        assert!("[provider]\nconcurrency = 0".parse::<Config>().is_err());
+        assert!("[provider]\nconcurrency = -4".parse::<Config>().is_err());
+
+        let config = "[provider]\nbackend = \"html\"".parse::<Config>().unwrap();
+        assert_eq!(config.provider.backend, Backend::Html);
+        assert!("[provider]\nbackend = \"xml\"".parse::<Config>().is_err());
+
+        let config = "[provider]\nretries = 2\ntimeout = 500"
+            .parse::<Config>()
+            .unwrap();
+        assert_eq!(config.provider.retries, 2);
+        assert_eq!(config.provider.timeout, Duration::from_millis(500));
+        assert!("[provider]\nretries = -1".parse::<Config>().is_err());
+        assert!("[provider]\nretries = 5000000000"
+            .parse::<Config>()
+            .is_err());
+        assert!("[provider]\nbackoff = -100".parse::<Config>().is_err());
+    }
+
+    #[test]
+    fn parse_cache_toml() {
+        let config = "[cache]\nsize = 2\nsearch = 0".parse::<Config>().unwrap();
+        assert_eq!(config.cache.max_size, 2 * 1024 * 1024);
+        assert_eq!(config.cache.search, Duration::from_secs(0));
+        assert!("[cache]\nstream = 10".parse::<Config>().is_err());
+        assert!("[cache]\nsize = -1".parse::<Config>().is_err());
+        assert!("[cache]\nsize = 9223372036854775807"
+            .parse::<Config>()
+            .is_err());
+        assert!("[cache]\nlanding = -60".parse::<Config>().is_err());
+    }
+
+    #[test]
+    fn parse_offline_toml() {
+        let config = "[offline]\nsize = 512".parse::<Config>().unwrap();
+        assert_eq!(config.offline.max_size, 512 * 1024 * 1024);
+        assert!("[offline]\nsize = -512".parse::<Config>().is_err());
+        assert!("[offline]\nsize = 9223372036854775807"
+            .parse::<Config>()
+            .is_err());
+    }
 }
@@ -1,14 +1,20 @@
+use std::collections::HashSet;
 use std::io::{stdout, Error, Stdout};
 
+use itertools::Itertools;
 use log::Level;
 use termion::raw::{IntoRawMode, RawTerminal};
 use tui::backend::TermionBackend;
+use tui::buffer::Buffer;
 use tui::layout::{Alignment, Constraint, Direction, Layout, Rect};
 use tui::style::{Color, Modifier, Style};
 use tui::terminal::Frame;
 use tui::widgets::{Block, Borders, List, Paragraph, Text, Widget};
 use tui::Terminal;
 
+use crate::edit::InsertBuffer;
+use crate::filter::fuzzy_match;
+use crate::meta::{Track, TrackId};
 use crate::view;
 
 type Backend = TermionBackend<RawTerminal<Stdout>>;
@@ -49,14 +55,30 @@ impl Drawer {
                 .margin(1)
                 .constraints(constraints)
                 .split(frame.size());
-            let texts = [Text::styled(
-                main_view.insert_buffer(),
-                Style::default().fg(Color::Gray).modifier(Modifier::BOLD),
-            )];
+            let style = Style::default().fg(Color::Gray).modifier(Modifier::BOLD);
+            let (title, texts) = match main_view.prompt() {
+                Some(prompt) => (
+                    prompt.kind.name(),
+                    vec![Text::styled(
+                        format!("{}{}", prompt.kind.prefix(), prompt.buffer),
+                        style,
+                    )],
+                ),
+                None => (
+                    main_view.view().name(),
+                    with_caret(main_view.insert_buffer(), style),
+                ),
+            };
+            let account = match (main_view.logged_in(), main_view.library()) {
+                (true, true) => "logged in",
+                (true, false) => "cookie, no library",
+                (false, _) => "anonymous",
+            };
+            let title = format!("{} [{}]", title, account);
             Paragraph::new(texts.iter())
                 .block(
                     Block::default()
-                        .title(main_view.view().name())
+                        .title(&title)
                         .title_style(Style::default().fg(Color::Magenta).modifier(Modifier::BOLD))
                         .borders(Borders::ALL),
                 )
@@ -64,7 +86,20 @@ impl Drawer {
                 .wrap(true)
                 .render(&mut frame, chunks[0]);
 
-            main_view.view().draw_at(&mut frame, chunks[1]);
+            main_view.view().draw_at(
+                &mut frame,
+                chunks[1],
+                main_view.spinner(),
+                main_view.offline(),
+            );
+
+            if let Some(help) = main_view.help() {
+                help.draw_at(&mut frame, chunks[1]);
+            }
+
+            if let Some(cursor) = main_view.job_list() {
+                draw_jobs(&mut frame, chunks[1], main_view.jobs(), cursor);
+            }
 
             if chunks.len() >= 3 {
                 let line = log_lines
@@ -96,99 +131,366 @@ impl Drawer {
 }
 
 impl view::View {
-    fn draw_at(&self, frame: &mut Frame<Backend>, chunk: Rect) {
+    fn draw_at(
+        &self,
+        frame: &mut Frame<Backend>,
+        chunk: Rect,
+        spinner: Option<char>,
+        offline: &HashSet<TrackId>,
+    ) {
         match self {
-            view::View::ArtistSearch(search) => search.draw_at(frame, chunk),
-            view::View::AlbumSearch(search) => search.draw_at(frame, chunk),
-            view::View::TrackList(list) => list.draw_at(frame, chunk),
-            view::View::Playlist(playlist) => playlist.draw_at(frame, chunk),
+            view::View::ArtistSearch(search) => search.draw_at(frame, chunk, spinner),
+            view::View::AlbumSearch(search) => search.draw_at(frame, chunk, spinner),
+            view::View::TrackList(list) => list.draw_at(frame, chunk, spinner, offline),
+            view::View::PlaylistSearch(search) => search.draw_at(frame, chunk, spinner),
+            view::View::Menu(menu) => menu.draw_at(frame, chunk, spinner),
+            view::View::ArtistPage(page) => page.draw_at(frame, chunk, spinner),
+            view::View::Playlist(playlist) => playlist.draw_at(frame, chunk, spinner, offline),
+            view::View::Downloads(downloads) => downloads.draw_at(frame, chunk, spinner),
         }
     }
 }
 
+/// Track label, the downloaded tracks are marked
+fn track_label(track: &Track, offline: &HashSet<TrackId>) -> String {
+    mark_offline(view::track_label(track), track, offline)
+}
+
+fn mark_offline(label: String, track: &Track, offline: &HashSet<TrackId>) -> String {
+    if offline.contains(&track.track_id) {
+        format!("{} [offline]", label)
+    } else {
+        label
+    }
+}
+
+/// List title with the spinner of the running jobs
+fn title(name: &str, spinner: Option<char>) -> String {
+    match spinner {
+        Some(spinner) => format!("{} {}", name, spinner),
+        None => name.to_string(),
+    }
+}
+
 impl view::ArtistSearch {
-    fn draw_at(&self, mut frame: &mut Frame<Backend>, chunk: Rect) {
-        List::new(cursored_line(
-            self.cached_artists.iter().map(|album| &album.name),
-            self.cursor,
+    fn draw_at(&self, frame: &mut Frame<Backend>, chunk: Rect, spinner: Option<char>) {
+        let labels = self.cached_artists.iter().map(view::artist_label);
+        draw_list(
+            frame,
             chunk,
-        ))
-        .block(Block::default().title("Artists").borders(Borders::ALL))
-        .render(&mut frame, chunk);
+            &title("Artists", spinner),
+            labels,
+            self.cursor,
+            self.filter.as_ref(),
+        );
     }
 }
 
 impl view::AlbumSearch {
-    fn draw_at(&self, mut frame: &mut Frame<Backend>, chunk: Rect) {
-        List::new(cursored_line(
-            self.cached_albums.iter().map(|album| {
-                if let Some(ref version) = album.version {
-                    format!(
-                        "{}: {} (year: {}, {})",
-                        album
-                            .artists
-                            .get(0)
-                            .map(|a| a.name.as_str())
-                            .unwrap_or("unknown"),
-                        album.title,
-                        album.year,
-                        version
-                    )
-                } else {
-                    format!(
-                        "{}: {} (year: {})",
-                        album
-                            .artists
-                            .get(0)
-                            .map(|a| a.name.as_str())
-                            .unwrap_or("unknown"),
-                        album.title,
-                        album.year
-                    )
-                }
-            }),
-            self.cursor,
+    fn draw_at(&self, frame: &mut Frame<Backend>, chunk: Rect, spinner: Option<char>) {
+        let labels = self.cached_albums.iter().map(view::album_label);
+        draw_list(
+            frame,
             chunk,
-        ))
-        .block(Block::default().title("Albums").borders(Borders::ALL))
-        .render(&mut frame, chunk);
+            &title("Albums", spinner),
+            labels,
+            self.cursor,
+            self.filter.as_ref(),
+        );
     }
 }
 
 impl view::TrackList {
-    fn draw_at(&self, mut frame: &mut Frame<Backend>, chunk: Rect) {
-        List::new(cursored_line(
-            self.cached_tracks.iter().map(|track| {
-                format!(
-                    "{} ({})",
-                    track.name,
-                    itertools::join(track.artists.iter().map(|a| a.name.as_str()), ", ")
-                )
-            }),
+    fn draw_at(
+        &self,
+        frame: &mut Frame<Backend>,
+        chunk: Rect,
+        spinner: Option<char>,
+        offline: &HashSet<TrackId>,
+    ) {
+        let labels = self
+            .cached_tracks
+            .iter()
+            .map(|track| track_label(track, offline));
+        draw_list(
+            frame,
+            chunk,
+            &title("Found Tracks", spinner),
+            labels,
+            self.cursor,
+            self.filter.as_ref(),
+        );
+    }
+}
+
+impl view::PlaylistSearch {
+    fn draw_at(&self, frame: &mut Frame<Backend>, chunk: Rect, spinner: Option<char>) {
+        let labels = self.cached_playlists.iter().map(view::playlist_label);
+        draw_list(
+            frame,
+            chunk,
+            &title("Playlists", spinner),
+            labels,
             self.cursor,
+            self.filter.as_ref(),
+        );
+    }
+}
+
+impl view::Menu {
+    fn draw_at(&self, frame: &mut Frame<Backend>, chunk: Rect, spinner: Option<char>) {
+        let labels = self.items.iter().map(view::menu_label);
+        draw_list(
+            frame,
             chunk,
-        ))
-        .block(Block::default().title("Found Tracks").borders(Borders::ALL))
-        .render(&mut frame, chunk);
+            &title(&self.title, spinner),
+            labels,
+            self.cursor,
+            self.filter.as_ref(),
+        );
+    }
+}
+
+impl view::ArtistPage {
+    fn draw_at(&self, frame: &mut Frame<Backend>, chunk: Rect, spinner: Option<char>) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(6), Constraint::Min(0)].as_ref())
+            .split(chunk);
+
+        let mut texts = vec![];
+        if !self.page.genres.is_empty() {
+            let genres = format!("Genres: {}\n", self.page.genres.join(", "));
+            texts.push(Text::styled(genres, Style::default().fg(Color::Cyan)));
+        }
+        texts.push(Text::raw(self.page.bio.as_deref().unwrap_or("No bio")));
+        Paragraph::new(texts.iter())
+            .wrap(true)
+            .block(
+                Block::default()
+                    .title(&self.page.artist.name)
+                    .borders(Borders::ALL),
+            )
+            .render(frame, chunks[0]);
+
+        let labels = self
+            .sections
+            .iter()
+            .map(|section| self.section_label(*section));
+        draw_list(
+            frame,
+            chunks[1],
+            &title("Artist", spinner),
+            labels,
+            self.cursor,
+            self.filter.as_ref(),
+        );
     }
 }
 
 impl view::Playlist {
-    fn draw_at(&self, mut frame: &mut Frame<Backend>, chunk: Rect) {
-        List::new(cursored_line(
-            self.tracks.iter().map(|track| {
-                format!(
-                    "{} ({})",
-                    track.name,
-                    itertools::join(track.artists.iter().map(|a| a.name.as_str()), ", ")
-                )
-            }),
-            self.current,
+    fn draw_at(
+        &self,
+        frame: &mut Frame<Backend>,
+        chunk: Rect,
+        spinner: Option<char>,
+        offline: &HashSet<TrackId>,
+    ) {
+        let labels = self
+            .tracks
+            .iter()
+            .map(|track| mark_offline(view::queue_label(track), track, offline));
+        let name = match self.station {
+            Some(ref station) => format!("Radio: {}", station),
+            None => "Playlist".to_string(),
+        };
+        let name = format!(
+            "{} ({} tracks, {})",
+            name,
+            self.tracks.len(),
+            view::duration_label(self.total_duration())
+        );
+        draw_list(
+            frame,
             chunk,
-        ))
-        .block(Block::default().title("Playlist").borders(Borders::ALL))
-        .render(&mut frame, chunk);
+            &title(&name, spinner),
+            labels,
+            self.current,
+            None,
+        );
+    }
+}
+
+impl view::Downloads {
+    fn draw_at(&self, frame: &mut Frame<Backend>, chunk: Rect, spinner: Option<char>) {
+        // the latest exports go first
+        let labels = self.exports.iter().rev().map(view::export_label);
+        List::new(labels.map(Text::raw))
+            .block(
+                Block::default()
+                    .title(&title("Downloads", spinner))
+                    .borders(Borders::ALL),
+            )
+            .render(frame, chunk);
+    }
+}
+
+fn draw_list(
+    frame: &mut Frame<Backend>,
+    chunk: Rect,
+    title: &str,
+    labels: impl Iterator<Item = String>,
+    cursor: usize,
+    filter: Option<&view::Filter>,
+) {
+    let filter = if let Some(filter) = filter {
+        filter
+    } else {
+        List::new(cursored_line(labels, cursor, chunk))
+            .block(Block::default().title(title).borders(Borders::ALL))
+            .render(frame, chunk);
+        return;
+    };
+
+    let labels = labels.collect::<Vec<_>>();
+    let position = filter
+        .matches
+        .iter()
+        .position(|i| *i == cursor)
+        .unwrap_or(0);
+    let skip = position.saturating_sub(usize::from(chunk.height) / 2);
+
+    let mut texts = vec![];
+    for (i, &item) in filter.matches.iter().enumerate().skip(skip) {
+        let label = &labels[item];
+        let matched = fuzzy_match(&filter.pattern, label).unwrap_or_default();
+        let base = if i == position {
+            Style::default()
+                .bg(Color::Gray)
+                .fg(Color::Black)
+                .modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        let highlight = base.fg(Color::Yellow).modifier(Modifier::BOLD);
+
+        // consecutive chars with the same highlighting are grouped together
+        let groups = label
+            .chars()
+            .enumerate()
+            .group_by(|(pos, _)| matched.contains(pos));
+        for (is_matched, group) in groups.into_iter() {
+            let part = group.map(|(_, c)| c).collect::<String>();
+            texts.push(Text::styled(
+                part,
+                if is_matched { highlight } else { base },
+            ));
+        }
+        texts.push(Text::raw("\n"));
+    }
+
+    let title = format!("{} /{}", title, filter.pattern);
+    Paragraph::new(texts.iter())
+        .block(Block::default().title(&title).borders(Borders::ALL))
+        .render(frame, chunk);
+}
+
+impl view::Help {
+    fn draw_at(&self, frame: &mut Frame<Backend>, chunk: Rect) {
+        let area = centered(chunk, 80, 90);
+
+        let header = Style::default().fg(Color::Magenta).modifier(Modifier::BOLD);
+        let lines = self.sections.iter().flat_map(|section| {
+            let bindings = section.bindings.iter().map(|(action, events)| {
+                let events = if events.is_empty() {
+                    "-".to_string()
+                } else {
+                    events.join(", ")
+                };
+                Text::raw(format!("  {:<18}{}", action, events))
+            });
+            std::iter::once(Text::styled(section.title, header)).chain(bindings)
+        });
+
+        Clear.render(frame, area);
+        List::new(lines.skip(self.scroll))
+            .block(
+                Block::default()
+                    .title("Help")
+                    .title_style(header)
+                    .borders(Borders::ALL),
+            )
+            .render(frame, area);
+    }
+}
+
+/// Overlay with the running background jobs
+fn draw_jobs(frame: &mut Frame<Backend>, chunk: Rect, jobs: &[view::JobStatus], cursor: usize) {
+    let area = centered(chunk, 60, 50);
+    let header = Style::default().fg(Color::Magenta).modifier(Modifier::BOLD);
+
+    Clear.render(frame, area);
+    let block = Block::default()
+        .title("Jobs")
+        .title_style(header)
+        .borders(Borders::ALL);
+    if jobs.is_empty() {
+        Paragraph::new([Text::raw("no running jobs")].iter())
+            .block(block)
+            .alignment(Alignment::Center)
+            .render(frame, area);
+        return;
+    }
+
+    let lines = jobs
+        .iter()
+        .map(|job| format!("{} ({:.1}s)", job.name, job.elapsed.as_secs_f32()));
+    List::new(cursored_line(lines, cursor, area))
+        .block(block)
+        .render(frame, area);
+}
+
+/// Line of the insert buffer with the char under the cursor highlighted
+fn with_caret(buffer: &InsertBuffer, style: Style) -> Vec<Text<'_>> {
+    let (before, after) = buffer.split_at_cursor();
+    if before.is_empty() && after.is_empty() {
+        return vec![];
     }
+
+    let caret_len = after.chars().next().map(char::len_utf8).unwrap_or(0);
+    let (caret, after) = after.split_at(caret_len);
+    let caret = if caret.is_empty() { " " } else { caret };
+
+    vec![
+        Text::styled(before, style),
+        Text::styled(caret, style.modifier(Modifier::REVERSED)),
+        Text::styled(after, style),
+    ]
+}
+
+/// Wipes the area out, so that the widgets drawn above do not mix with the previous ones
+struct Clear;
+
+impl Widget for Clear {
+    fn draw(&mut self, area: Rect, buf: &mut Buffer) {
+        for x in area.left()..area.right() {
+            for y in area.top()..area.bottom() {
+                buf.get_mut(x, y).reset();
+            }
+        }
+    }
+}
+
+/// Rect inside of `chunk` with the specified size in percents
+fn centered(chunk: Rect, width: u16, height: u16) -> Rect {
+    let width = chunk.width * width / 100;
+    let height = chunk.height * height / 100;
+    Rect::new(
+        chunk.x + (chunk.width - width) / 2,
+        chunk.y + (chunk.height - height) / 2,
+        width,
+        height,
+    )
 }
 
 fn cursored_line<'a>(
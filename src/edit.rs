@@ -0,0 +1,174 @@
+use std::ops::Deref;
+
+// This file contains readline-like editing of a single line
+
+/// Text with a cursor, the cursor is a byte offset at a char boundary
+#[derive(Debug, Clone, Default)]
+pub struct InsertBuffer {
+    text: String,
+    cursor: usize,
+}
+
+impl InsertBuffer {
+    /// Replaces the text and puts the cursor at the end
+    pub fn set(&mut self, text: String) {
+        self.cursor = text.len();
+        self.text = text;
+    }
+
+    pub fn clear(&mut self) {
+        self.set(String::new());
+    }
+
+    pub fn split_at_cursor(&self) -> (&str, &str) {
+        self.text.split_at(self.cursor)
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    pub fn backspace(&mut self) {
+        let prev = self.prev_char();
+        self.text.replace_range(prev..self.cursor, "");
+        self.cursor = prev;
+    }
+
+    pub fn left(&mut self) {
+        self.cursor = self.prev_char();
+    }
+
+    pub fn right(&mut self) {
+        self.cursor = self.next_char();
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    /// Moves to the start of the current or the previous word
+    pub fn word_left(&mut self) {
+        self.cursor = self.word_start(|c| !c.is_alphanumeric());
+    }
+
+    /// Moves to the end of the current or the next word
+    pub fn word_right(&mut self) {
+        let after = &self.text[self.cursor..];
+        let word = after
+            .char_indices()
+            .skip_while(|(_, c)| !c.is_alphanumeric())
+            .find(|(_, c)| !c.is_alphanumeric())
+            .map(|(i, _)| i)
+            .unwrap_or_else(|| after.len());
+        self.cursor += word;
+    }
+
+    /// Deletes a whitespace delimited word before the cursor, i.e. Ctrl+W
+    pub fn delete_word(&mut self) {
+        let start = self.word_start(char::is_whitespace);
+        self.text.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    /// Deletes everything before the cursor, i.e. Ctrl+U
+    pub fn delete_to_start(&mut self) {
+        self.text.replace_range(..self.cursor, "");
+        self.cursor = 0;
+    }
+
+    fn prev_char(&self) -> usize {
+        self.text[..self.cursor]
+            .char_indices()
+            .last()
+            .map(|(i, _)| i)
+            .unwrap_or(0)
+    }
+
+    fn next_char(&self) -> usize {
+        self.text[self.cursor..]
+            .chars()
+            .next()
+            .map(|c| self.cursor + c.len_utf8())
+            .unwrap_or(self.cursor)
+    }
+
+    fn word_start(&self, is_delimiter: impl Fn(char) -> bool) -> usize {
+        let before = &self.text[..self.cursor];
+        let mut chars = before
+            .char_indices()
+            .rev()
+            .skip_while(|(_, c)| is_delimiter(*c));
+        let mut start = match chars.next() {
+            Some((i, _)) => i,
+            None => return 0,
+        };
+        for (i, c) in chars {
+            if is_delimiter(c) {
+                break;
+            }
+            start = i;
+        }
+        start
+    }
+}
+
+impl Deref for InsertBuffer {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(text: &str) -> InsertBuffer {
+        let mut buffer = InsertBuffer::default();
+        buffer.set(text.to_string());
+        buffer
+    }
+
+    fn column(buffer: &InsertBuffer) -> usize {
+        buffer.split_at_cursor().0.chars().count()
+    }
+
+    #[test]
+    fn test_editing() {
+        let mut line = buffer("пинк флойд");
+        line.left();
+        line.backspace();
+        line.insert('й');
+        assert_eq!(&*line, "пинк флойд");
+        assert_eq!(column(&line), 9);
+
+        line.word_left();
+        assert_eq!(column(&line), 5);
+        line.word_left();
+        assert_eq!(column(&line), 0);
+        line.word_right();
+        assert_eq!(column(&line), 4);
+
+        line.end();
+        line.delete_word();
+        assert_eq!(&*line, "пинк ");
+        line.delete_to_start();
+        assert!(line.is_empty());
+    }
+
+    #[test]
+    fn test_word_deletion() {
+        let mut line = buffer("the wall, part 2  ");
+        line.delete_word();
+        assert_eq!(&*line, "the wall, part ");
+        line.home();
+        line.delete_word();
+        assert_eq!(&*line, "the wall, part ");
+    }
+}
@@ -0,0 +1,408 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use futures::stream::{self, StreamExt};
+use id3::frame::{Picture, PictureType};
+use id3::{Tag, TagLike, Version};
+use snafu::ResultExt;
+
+use crate::meta::{Album, Artist, Track, TrackId};
+use crate::offline::TrackCache;
+use crate::providers::{self, Provider};
+
+// This file contains an export of the tracks into a music folder as tagged files
+
+#[derive(Debug, snafu::Snafu)]
+pub enum Error {
+    #[snafu(display("cannot download {}: {}", track, source))]
+    Download {
+        track: String,
+        source: providers::Error,
+    },
+    #[snafu(display("cannot write {}: {}", path.display(), source))]
+    Write { path: PathBuf, source: io::Error },
+    #[snafu(display("cannot tag {}: {}", path.display(), source))]
+    Tagging { path: PathBuf, source: id3::Error },
+}
+
+/// Tracks to export
+#[derive(Debug, Clone)]
+pub enum Request {
+    /// Tracks of the album, all of them unless some are picked
+    Album {
+        album: Album,
+        picked: Option<Vec<TrackId>>,
+    },
+    /// All the albums of the artist
+    Discography(Artist),
+}
+
+/// Progress of an export, shown at the downloads view
+#[derive(Debug, Clone)]
+pub struct Progress {
+    pub name: String,
+    pub done: usize,
+    pub failed: usize,
+    /// Amount of the tracks known so far, albums of a discography are loaded one by one
+    pub total: usize,
+}
+
+/// Exports the tracks, downloaded ones are taken from the offline cache
+#[derive(Clone)]
+pub struct Exporter {
+    provider: Provider,
+    tracks: Arc<TrackCache>,
+    dir: PathBuf,
+    concurrency: usize,
+    progress: Arc<Mutex<Vec<Progress>>>,
+}
+
+impl Exporter {
+    pub fn new(
+        provider: Provider,
+        tracks: Arc<TrackCache>,
+        dir: PathBuf,
+        concurrency: usize,
+    ) -> Self {
+        Self {
+            provider,
+            tracks,
+            dir,
+            concurrency,
+            progress: Arc::default(),
+        }
+    }
+
+    /// Progress of every started export
+    pub fn progress(&self) -> Vec<Progress> {
+        self.progress.lock().unwrap().clone()
+    }
+
+    fn update(&self, index: usize, f: impl FnOnce(&mut Progress)) {
+        f(&mut self.progress.lock().unwrap()[index]);
+    }
+
+    /// Exports the tracks, failures of particular tracks are logged and counted
+    pub async fn export(&self, name: String, request: Request) -> providers::Result<Progress> {
+        let index = {
+            let mut progress = self.progress.lock().unwrap();
+            progress.push(Progress {
+                name,
+                done: 0,
+                failed: 0,
+                total: 0,
+            });
+            progress.len() - 1
+        };
+
+        let albums = match request {
+            Request::Album { album, picked } => vec![(album, picked)],
+            Request::Discography(artist) => {
+                let albums = self.provider.artist_albums(&artist).await?.albums;
+                albums.into_iter().map(|album| (album, None)).collect()
+            }
+        };
+
+        for (album, picked) in albums {
+            let tracks = match self.provider.album_tracks(&album).await {
+                Ok(tracks) => tracks.tracks,
+                Err(err) => {
+                    // the other albums are exported anyway, the failed one is counted by its tracks
+                    log::error!("cannot get the tracks of {}: {}", album.title, err);
+                    let missed = match picked {
+                        Some(ref picked) => picked.len(),
+                        None => album.track_count.map_or(1, |count| count as usize),
+                    };
+                    self.update(index, |progress| {
+                        progress.total += missed;
+                        progress.failed += missed;
+                    });
+                    continue;
+                }
+            };
+            let cover = self
+                .provider
+                .album_cover(&album)
+                .await
+                .unwrap_or_else(|err| {
+                    log::warn!("cannot get the cover of {}: {}", album.title, err);
+                    None
+                });
+
+            let numbers = track_numbers(&tracks);
+            let tracks = tracks
+                .into_iter()
+                .zip(numbers)
+                .filter(|(track, _)| match picked {
+                    Some(ref picked) => picked.contains(&track.track_id),
+                    None => true,
+                })
+                .collect::<Vec<_>>();
+            self.update(index, |progress| progress.total += tracks.len());
+
+            let album = &album;
+            let cover = cover.as_deref();
+            stream::iter(tracks)
+                .for_each_concurrent(self.concurrency, |(track, number)| async move {
+                    let exported = self.export_track(album, number, &track, cover).await;
+                    self.update(index, |progress| match exported {
+                        Ok(()) => progress.done += 1,
+                        Err(err) => {
+                            log::error!("{}", err);
+                            progress.failed += 1;
+                        }
+                    });
+                })
+                .await;
+        }
+
+        Ok(self.progress.lock().unwrap()[index].clone())
+    }
+
+    async fn export_track(
+        &self,
+        album: &Album,
+        number: TrackNumber,
+        track: &Track,
+        cover: Option<&[u8]>,
+    ) -> Result<(), Error> {
+        let cached = self
+            .tracks
+            .get(track.track_id)
+            .and_then(|path| fs::read(path).ok());
+        let data = match cached {
+            Some(data) => data,
+            None => self
+                .provider
+                .download_track(track)
+                .await
+                .context(Download {
+                    track: track.name.as_str(),
+                })?,
+        };
+
+        let path = track_path(&self.dir, album, number, track);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context(Write { path: dir })?;
+        }
+        fs::write(&path, data).context(Write { path: &path })?;
+        track_tag(album, number, track, cover)
+            .write_to_path(&path, Version::Id3v24)
+            .context(Tagging { path })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TrackNumber {
+    /// Position at the disc, counted from one
+    number: usize,
+    /// Amount of the disc tracks
+    count: usize,
+    /// Disc of a multi-disc album
+    disc: Option<u16>,
+}
+
+/// Numbers of the album tracks. The tracks of a multi-disc album are numbered per disc, unless
+/// some positions are unknown, then the tracks are numbered through the album.
+fn track_numbers(tracks: &[Track]) -> Vec<TrackNumber> {
+    let per_disc = tracks
+        .iter()
+        .all(|track| track.disc.is_some() && track.number.is_some());
+    let mut discs = tracks
+        .iter()
+        .filter_map(|track| track.disc)
+        .collect::<Vec<_>>();
+    discs.sort();
+    discs.dedup();
+
+    tracks
+        .iter()
+        .enumerate()
+        .map(|(position, track)| match (track.disc, track.number) {
+            (Some(disc), Some(number)) if per_disc => TrackNumber {
+                number: usize::from(number),
+                count: tracks
+                    .iter()
+                    .filter(|track| track.disc == Some(disc))
+                    .count(),
+                disc: Some(disc).filter(|_| discs.len() > 1),
+            },
+            _ => TrackNumber {
+                number: position + 1,
+                count: tracks.len(),
+                disc: None,
+            },
+        })
+        .collect()
+}
+
+/// Replaces the chars, which are not allowed at file names on some systems.
+/// Empty names and the ones of dots only, like `..`, are replaced as well.
+fn file_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim()
+        .to_string();
+    if name.chars().all(|c| c == '.') {
+        "_".repeat(name.len().max(1))
+    } else {
+        name
+    }
+}
+
+fn album_artist(album: &Album) -> &str {
+    album
+        .artists
+        .first()
+        .map_or("Unknown Artist", |artist| artist.name.as_str())
+}
+
+/// `Artist/Year - Album/NN - Title.mp3` at the export directory, the tracks of a multi-disc
+/// album are named `D-NN - Title.mp3`
+fn track_path(dir: &Path, album: &Album, number: TrackNumber, track: &Track) -> PathBuf {
+    let position = match number.disc {
+        Some(disc) => format!("{}-{:02}", disc, number.number),
+        None => format!("{:02}", number.number),
+    };
+    dir.join(file_name(album_artist(album)))
+        .join(file_name(&format!("{} - {}", album.year, album.title)))
+        .join(file_name(&format!("{} - {}.mp3", position, track.name)))
+}
+
+fn track_tag(album: &Album, number: TrackNumber, track: &Track, cover: Option<&[u8]>) -> Tag {
+    let mut tag = Tag::new();
+    tag.set_title(track.name.as_str());
+    tag.set_artist(itertools::join(
+        track.artists.iter().map(|artist| artist.name.as_str()),
+        ", ",
+    ));
+    tag.set_album(album.title.as_str());
+    tag.set_album_artist(album_artist(album));
+    tag.set_year(i32::from(album.year));
+    tag.set_track(number.number as u32);
+    tag.set_total_tracks(number.count as u32);
+    if let Some(disc) = track.disc {
+        tag.set_disc(u32::from(disc));
+    }
+    if let Some(ref genre) = album.genre {
+        tag.set_genre(genre.as_str());
+    }
+    if let Some(ref label) = album.label {
+        tag.set_text("TPUB", label.as_str());
+    }
+    if let Some(cover) = cover {
+        tag.add_frame(Picture {
+            mime_type: "image/jpeg".to_string(),
+            picture_type: PictureType::CoverFront,
+            description: String::new(),
+            data: cover.to_vec(),
+        });
+    }
+    tag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meta::{AlbumId, ArtistId};
+
+    #[test]
+    fn test_track_path() {
+        let artist = Artist {
+            id: ArtistId::yandex(1),
+            name: "AC/DC".to_string(),
+        };
+        let album = Album {
+            artists: vec![artist.clone()],
+            year: 1980,
+            genre: Some("rock".to_string()),
+            ..Album::new(AlbumId::yandex(2), "Back in Black".to_string())
+        };
+        let track = Track::new(
+            album.id,
+            TrackId::yandex(3),
+            "What Do You Do for Money Honey?".to_string(),
+            vec![artist],
+        );
+
+        let number = TrackNumber {
+            number: 7,
+            count: 10,
+            disc: None,
+        };
+        assert_eq!(
+            track_path(Path::new("/music"), &album, number, &track),
+            Path::new("/music/AC_DC/1980 - Back in Black/07 - What Do You Do for Money Honey_.mp3")
+        );
+
+        let tag = track_tag(&album, number, &track, Some(b"cover"));
+        assert_eq!(tag.album_artist(), Some("AC/DC"));
+        assert_eq!((tag.track(), tag.total_tracks()), (Some(7), Some(10)));
+        assert_eq!(tag.genre(), Some("rock"));
+        assert_eq!(tag.pictures().count(), 1);
+    }
+
+    #[test]
+    fn test_multi_disc_numbers() {
+        let track = |disc, number| Track {
+            disc,
+            number,
+            ..Track::new(
+                AlbumId::yandex(1),
+                TrackId::yandex(1),
+                "Track".to_string(),
+                vec![],
+            )
+        };
+        let numbers = track_numbers(&[
+            track(Some(1), Some(1)),
+            track(Some(1), Some(2)),
+            track(Some(2), Some(1)),
+        ]);
+        let second = TrackNumber {
+            number: 2,
+            count: 2,
+            disc: Some(1),
+        };
+        assert_eq!(numbers[1], second);
+        assert_eq!((numbers[2].number, numbers[2].count), (1, 1));
+
+        let path = track_path(
+            Path::new("/music"),
+            &Album::new(AlbumId::yandex(1), "Album".to_string()),
+            numbers[2],
+            &track(None, None),
+        );
+        assert!(path.ends_with("2-01 - Track.mp3"));
+
+        // positions are flat, once some are unknown
+        let numbers = track_numbers(&[track(Some(1), Some(1)), track(Some(2), None)]);
+        assert_eq!(
+            numbers[1],
+            TrackNumber {
+                number: 2,
+                count: 2,
+                disc: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(file_name(".."), "__");
+        assert_eq!(file_name("."), "_");
+        assert_eq!(file_name("  "), "_");
+        assert_eq!(
+            file_name("...And Justice for All"),
+            "...And Justice for All"
+        );
+    }
+}
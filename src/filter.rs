@@ -0,0 +1,38 @@
+// This file contains fuzzy matching for the in-list filter
+
+fn same_char(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+/// Matches the pattern chars as a case-insensitive subsequence of the text.
+/// Returns char positions of the text, at which the pattern chars are found.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<Vec<usize>> {
+    let mut positions = Vec::with_capacity(pattern.len());
+    let mut text = text.chars().enumerate();
+
+    for p in pattern.chars().filter(|c| !c.is_whitespace()) {
+        let (pos, _) = text.find(|(_, c)| same_char(*c, p))?;
+        positions.push(pos);
+    }
+    Some(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(
+            fuzzy_match("dsm", "Dark Side of the Moon"),
+            Some(vec![0, 5, 17])
+        );
+        assert_eq!(
+            fuzzy_match("side moon", "Dark Side of the Moon").map(|p| p.len()),
+            Some(8)
+        );
+        assert_eq!(fuzzy_match("ЛЕТО", "Кино: Лето"), Some(vec![6, 7, 8, 9]));
+        assert_eq!(fuzzy_match("mx", "Moon"), None);
+        assert_eq!(fuzzy_match("", "Moon"), Some(vec![]));
+    }
+}
@@ -0,0 +1,159 @@
+use std::fs;
+use std::io::{Error, Write};
+use std::path::PathBuf;
+
+use crate::view::SearchKind;
+
+const MAX_ENTRIES: usize = 200;
+
+/// Previous search queries, browsed by a typed prefix
+#[derive(Debug, Default)]
+pub struct History {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+    /// Browsed entry index and the text typed before browsing
+    browsing: Option<(usize, String)>,
+}
+
+impl History {
+    fn load(path: Option<PathBuf>) -> Self {
+        let entries = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| content.lines().map(str::to_string).collect())
+            .unwrap_or_default();
+
+        Self {
+            entries,
+            path,
+            browsing: None,
+        }
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut file = fs::File::create(path)?;
+        for entry in self.entries.iter() {
+            writeln!(file, "{}", entry)?;
+        }
+        Ok(())
+    }
+
+    /// Appends the query as the most recent one and stores the history on disk
+    pub fn push(&mut self, query: &str) -> Result<(), Error> {
+        self.browsing = None;
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(());
+        }
+
+        self.entries.retain(|entry| entry != query);
+        self.entries.push(query.to_string());
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.drain(..self.entries.len() - MAX_ENTRIES);
+        }
+        self.save()
+    }
+
+    /// Older entry starting with the text typed before browsing
+    pub fn prev(&mut self, current: &str) -> Option<&str> {
+        let (end, typed) = match self.browsing.take() {
+            Some(browsing) => browsing,
+            None => (self.entries.len(), current.to_string()),
+        };
+
+        let found = self.entries[..end]
+            .iter()
+            .rposition(|entry| entry.starts_with(&typed));
+        self.browsing = Some((found.unwrap_or(end), typed));
+        found.map(move |i| self.entries[i].as_str())
+    }
+
+    /// Newer entry starting with the typed text, or the typed text itself
+    pub fn next(&mut self) -> Option<String> {
+        let (start, typed) = self.browsing.take()?;
+
+        let found = self.entries[start..]
+            .iter()
+            .skip(1)
+            .position(|entry| entry.starts_with(&typed))
+            .map(|i| start + 1 + i);
+        match found {
+            Some(i) => {
+                self.browsing = Some((i, typed));
+                Some(self.entries[i].clone())
+            }
+            None => Some(typed),
+        }
+    }
+
+    /// Stops browsing, e.g. after the text is edited
+    pub fn reset(&mut self) {
+        self.browsing = None;
+    }
+}
+
+/// Search histories for every search kind, stored in the data directory
+#[derive(Debug, Default)]
+pub struct Histories {
+    albums: History,
+    tracks: History,
+    artists: History,
+    playlists: History,
+}
+
+impl Histories {
+    pub fn load() -> Self {
+        let path = |name: &str| {
+            dirs::data_dir().map(|mut path| {
+                path.push("rum-player");
+                path.push("history");
+                path.push(name);
+                path
+            })
+        };
+
+        Self {
+            albums: History::load(path("albums")),
+            tracks: History::load(path("tracks")),
+            artists: History::load(path("artists")),
+            playlists: History::load(path("playlists")),
+        }
+    }
+
+    pub fn get_mut(&mut self, kind: SearchKind) -> &mut History {
+        match kind {
+            SearchKind::Album => &mut self.albums,
+            SearchKind::Track => &mut self.tracks,
+            SearchKind::Artist => &mut self.artists,
+            SearchKind::Playlist => &mut self.playlists,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix_browsing() {
+        let mut history = History::default();
+        for query in &["kino", "pink floyd", "king crimson", "kino"] {
+            history.push(query).unwrap();
+        }
+
+        assert_eq!(history.prev("ki"), Some("kino"));
+        assert_eq!(history.prev("kino"), Some("king crimson"));
+        assert_eq!(history.prev("king crimson"), None);
+        assert_eq!(history.next(), Some("kino".to_string()));
+        assert_eq!(history.next(), Some("ki".to_string()));
+        assert_eq!(history.next(), None);
+    }
+}
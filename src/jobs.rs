@@ -0,0 +1,132 @@
+use std::time::Duration;
+
+use futures::channel::mpsc;
+use futures::future::{self, AbortHandle, Abortable, Future};
+use futures::prelude::*;
+use tokio::time::Instant;
+
+// This file contains a manager of the background jobs, whose results are awaited by the event loop
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JobId(usize);
+
+/// Running job, it is aborted when cancelled
+pub struct Job {
+    pub id: JobId,
+    pub name: String,
+    /// Name of the view, where the job is started
+    pub view: &'static str,
+    started: Instant,
+    abort: AbortHandle,
+}
+
+impl Job {
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+pub struct Jobs<T> {
+    next_id: usize,
+    running: Vec<Job>,
+    sender: mpsc::UnboundedSender<(JobId, T)>,
+    receiver: mpsc::UnboundedReceiver<(JobId, T)>,
+}
+
+impl<T: Send + 'static> Jobs<T> {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::unbounded();
+        Self {
+            next_id: 0,
+            running: vec![],
+            sender,
+            receiver,
+        }
+    }
+
+    /// Spawns the job at the runtime, its output is returned by `finished`
+    pub fn spawn(
+        &mut self,
+        name: impl Into<String>,
+        view: &'static str,
+        job: impl Future<Output = T> + Send + 'static,
+    ) -> JobId {
+        let id = JobId(self.next_id);
+        self.next_id += 1;
+
+        let (abort, registration) = AbortHandle::new_pair();
+        let sender = self.sender.clone();
+        let job = async move {
+            let output = job.await;
+            // the receiver lives as long as the manager, nobody waits otherwise
+            let _ = sender.unbounded_send((id, output));
+        };
+        tokio::spawn(Abortable::new(job, registration));
+
+        self.running.push(Job {
+            id,
+            name: name.into(),
+            view,
+            started: Instant::now(),
+            abort,
+        });
+        id
+    }
+
+    pub fn cancel(&mut self, id: JobId) -> Option<Job> {
+        let position = self.running.iter().position(|job| job.id == id)?;
+        let job = self.running.remove(position);
+        job.abort.abort();
+        Some(job)
+    }
+
+    /// Cancels the most recently started job
+    pub fn cancel_last(&mut self) -> Option<Job> {
+        let id = self.running.last()?.id;
+        self.cancel(id)
+    }
+
+    pub fn running(&self) -> &[Job] {
+        &self.running
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.running.is_empty()
+    }
+
+    /// Waits for any running job to finish, outputs of the cancelled ones are dropped
+    pub async fn finished(&mut self) -> (Job, T) {
+        while let Some((id, output)) = self.receiver.next().await {
+            if let Some(position) = self.running.iter().position(|job| job.id == id) {
+                return (self.running.remove(position), output);
+            }
+        }
+        future::pending().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cancelled_job_is_dropped() {
+        let mut jobs = Jobs::new();
+        let slow = jobs.spawn("slow", "view", async {
+            tokio::time::delay_for(Duration::from_millis(50)).await;
+            1
+        });
+        jobs.spawn("fast", "view", async { 2 });
+        jobs.spawn("cancelled", "view", async { 3 });
+
+        assert_eq!(
+            jobs.cancel_last().map(|job| job.name),
+            Some("cancelled".into())
+        );
+        let (job, output) = jobs.finished().await;
+        assert_eq!((job.name.as_str(), output), ("fast", 2));
+        let (job, output) = jobs.finished().await;
+        assert_eq!((job.id, output), (slow, 1));
+        assert!(jobs.is_empty());
+    }
+}
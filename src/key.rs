@@ -5,6 +5,8 @@ use std::sync::{Arc, Mutex};
 use futures::channel::mpsc;
 use futures::prelude::*;
 use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::{EnumIter, IntoStaticStr};
 use termion::event::{Event, Key};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -62,6 +64,25 @@ impl Context {
             is_playlist: true,
         }
     }
+
+    /// Context by its name at config, i.e. `search`, `tracklist` or `playlist`
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "search" => Some(Context::search()),
+            "tracklist" => Some(Context::tracklist()),
+            "playlist" => Some(Context::playlist()),
+            _ => None,
+        }
+    }
+
+    /// Contexts of every view, as they are set during the view display
+    pub fn views() -> [(&'static str, Context); 3] {
+        [
+            ("Search", Context::search()),
+            ("Track list", Context::search() | Context::tracklist()),
+            ("Playlist", Context::playlist()),
+        ]
+    }
 }
 
 impl BitOr for Context {
@@ -76,7 +97,7 @@ impl BitOr for Context {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumIter, IntoStaticStr)]
 pub enum Action {
     Quit,
     PointerUp,
@@ -92,6 +113,7 @@ pub enum Action {
     ShowPlaylist,
     SwitchToAlbums,
     SwitchToTracks,
+    SwitchToPlaylists,
     SwitchToArtists,
     Search,
     Select,
@@ -100,17 +122,245 @@ pub enum Action {
     #[serde(skip)]
     Char(char),
     Backspace,
+    ShowHelp,
+    CommandLine,
+    Complete,
+    Filter,
+    NextMatch,
+    PrevMatch,
+    CursorLeft,
+    CursorRight,
+    LineStart,
+    LineEnd,
+    WordLeft,
+    WordRight,
+    DeleteWord,
+    DeleteLine,
+    HistoryPrev,
+    HistoryNext,
+    Cancel,
+    ShowJobs,
+    Download,
+    Export,
+    ShowDownloads,
+    Logout,
+    ShowLikedTracks,
+    ShowLikedAlbums,
+    ShowFollowedArtists,
+    ShowMyPlaylists,
+    Like,
+    Unlike,
+    StartStation,
+    ShowStartMenu,
 }
 
+impl Action {
+    /// Actions which might be bound to a key
+    pub fn bindable() -> impl Iterator<Item = Action> {
+        Action::iter().filter(|action| !matches!(action, Action::Char(_)))
+    }
+
+    pub fn name(self) -> &'static str {
+        self.into()
+    }
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        Action::bindable().find(|action| action.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// Defines how the input events are interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Events are mapped via key bindings
+    Normal,
+    /// Events edit the prompt line, bindings are not used
+    Prompt,
+    /// Same as normal, but the chars jump between the filtered items
+    Filtered,
+    /// Same as normal, but the arrows and line editing keys edit the insert buffer
+    Insert,
+}
+
+impl Mode {
+    /// Modes with their own keys, as they are titled at the help
+    pub fn specific() -> [(&'static str, Mode); 3] {
+        [
+            ("Filter", Mode::Filtered),
+            ("Search input", Mode::Insert),
+            ("Command line", Mode::Prompt),
+        ]
+    }
+}
+
+/// Human readable event name, similar to the ones used at config
+pub fn event_name(event: &Event) -> String {
+    fn char_name(c: char) -> String {
+        match c {
+            '\n' | '\r' => "Enter".to_string(),
+            '\t' => "Tab".to_string(),
+            ' ' => "Space".to_string(),
+            '\x1b' => "Esc".to_string(),
+            c => c.to_string(),
+        }
+    }
+
+    let key = if let Event::Key(key) = event {
+        key
+    } else {
+        return format!("{:?}", event);
+    };
+
+    match key {
+        Key::Up => "ArrowUp".to_string(),
+        Key::Down => "ArrowDown".to_string(),
+        Key::Right => "ArrowRight".to_string(),
+        Key::Left => "ArrowLeft".to_string(),
+        Key::Delete => "Del".to_string(),
+        Key::Backspace => "Backspace".to_string(),
+        Key::Home => "Home".to_string(),
+        Key::End => "End".to_string(),
+        Key::PageUp => "PageUp".to_string(),
+        Key::PageDown => "PageDown".to_string(),
+        Key::Insert => "Insert".to_string(),
+        Key::Esc => "Esc".to_string(),
+        Key::F(n) => format!("F{}", n),
+        Key::Ctrl(c) => format!("Ctrl+{}", char_name(*c)),
+        Key::Alt(c) => format!("Alt+{}", char_name(*c)),
+        Key::Char(c) => char_name(*c),
+        key => format!("{:?}", key),
+    }
+}
+
+const DEFAULT_KEYS: &[(Key, Action)] = &[
+    (Key::Up, Action::PointerUp),
+    (Key::Down, Action::PointerDown),
+    (Key::Right, Action::NextTrack),
+    (Key::Left, Action::PrevTrack),
+    (Key::Delete, Action::Quit),
+    (Key::Ctrl('c'), Action::Quit),
+    (Key::Ctrl('p'), Action::FlipPause),
+    (Key::Char(']'), Action::Forward5),
+    (Key::Char('['), Action::Backward5),
+    (Key::Ctrl('r'), Action::Refresh),
+    (Key::Ctrl('s'), Action::Stop),
+    (Key::Ctrl('a'), Action::AddAll),
+    (Key::Alt('p'), Action::ShowPlaylist),
+    (Key::Alt('a'), Action::SwitchToAlbums),
+    (Key::Alt('t'), Action::SwitchToTracks),
+    (Key::Alt('y'), Action::SwitchToPlaylists),
+    (Key::Alt('s'), Action::SwitchToArtists),
+    (Key::Alt('\n'), Action::Search),
+    (Key::Alt('\r'), Action::Search),
+    (Key::Char('\n'), Action::Select),
+    (Key::Char('\r'), Action::Select),
+    (Key::Char('\t'), Action::SwitchView),
+    (Key::Backspace, Action::Backspace),
+    (Key::Alt('\x1b'), Action::PrevView),
+    (Key::F(1), Action::ShowHelp),
+    (Key::Char(':'), Action::CommandLine),
+    (Key::Char('/'), Action::Filter),
+    (Key::Home, Action::LineStart),
+    (Key::End, Action::LineEnd),
+    (Key::Ctrl('e'), Action::LineEnd),
+    (Key::Alt('b'), Action::WordLeft),
+    (Key::Alt('f'), Action::WordRight),
+    (Key::Ctrl('w'), Action::DeleteWord),
+    (Key::Ctrl('u'), Action::DeleteLine),
+    (Key::Ctrl('x'), Action::Cancel),
+    (Key::Alt('j'), Action::ShowJobs),
+    (Key::Alt('d'), Action::Download),
+    (Key::Alt('e'), Action::Export),
+    (Key::Alt('o'), Action::ShowDownloads),
+    (Key::Alt('1'), Action::ShowLikedTracks),
+    (Key::Alt('2'), Action::ShowLikedAlbums),
+    (Key::Alt('3'), Action::ShowFollowedArtists),
+    (Key::Alt('4'), Action::ShowMyPlaylists),
+    (Key::Alt('l'), Action::Like),
+    (Key::Alt('u'), Action::Unlike),
+    (Key::Alt('r'), Action::StartStation),
+    (Key::Alt('m'), Action::ShowStartMenu),
+];
+
+/// Keys taking priority over the bindings while the insert buffer is edited
+const INSERT_KEYS: &[(Key, Action)] = &[
+    (Key::Left, Action::CursorLeft),
+    (Key::Right, Action::CursorRight),
+    (Key::Ctrl('a'), Action::LineStart),
+    (Key::Up, Action::HistoryPrev),
+    (Key::Down, Action::HistoryNext),
+];
+
+/// Keys taking priority over the bindings while the filtered items are jumped between
+const FILTERED_KEYS: &[(Key, Action)] = &[
+    (Key::Char('n'), Action::NextMatch),
+    (Key::Char('N'), Action::PrevMatch),
+];
+
+/// Keys editing the prompt line, the other chars are typed
+const PROMPT_KEYS: &[(Key, Action)] = &[
+    (Key::Char('\n'), Action::Select),
+    (Key::Char('\r'), Action::Select),
+    (Key::Char('\t'), Action::Complete),
+    (Key::Alt('\x1b'), Action::PrevView),
+    (Key::Esc, Action::PrevView),
+    (Key::Backspace, Action::Backspace),
+    (Key::Ctrl('c'), Action::Quit),
+];
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ContextedAction {
     pub context: Context,
     pub action: Action,
 }
 
-#[derive(Default, Debug)]
+/// Maps characters of a non-latin keyboard layout onto the latin keys placed at the same position
+#[derive(Default, Debug, Clone)]
+pub struct Layout {
+    chars: HashMap<char, char>,
+}
+
+const JCUKEN_LOWER: &str = "йцукенгшщзхъфывапролджэячсмитьбюё";
+const JCUKEN_UPPER: &str = "ЙЦУКЕНГШЩЗХЪФЫВАПРОЛДЖЭЯЧСМИТЬБЮЁ";
+const QWERTY_LOWER: &str = "qwertyuiop[]asdfghjkl;'zxcvbnm,.`";
+const QWERTY_UPPER: &str = "QWERTYUIOP{}ASDFGHJKL:\"ZXCVBNM<>~";
+
+impl Layout {
+    /// Russian ЙЦУКЕН layout mapped onto QWERTY
+    pub fn jcuken() -> Self {
+        let lower = JCUKEN_LOWER.chars().zip(QWERTY_LOWER.chars());
+        let upper = JCUKEN_UPPER.chars().zip(QWERTY_UPPER.chars());
+        Self {
+            chars: lower.chain(upper).collect(),
+        }
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "jcuken" | "ru" => Some(Self::jcuken()),
+            _ => None,
+        }
+    }
+
+    pub fn insert(&mut self, from: char, to: char) {
+        self.chars.insert(from, to);
+    }
+
+    /// Only modified keys are translated, so plain chars still reach the insert buffer as typed
+    fn translate(&self, event: &Event) -> Option<Event> {
+        let key = match event {
+            Event::Key(Key::Alt(c)) => Key::Alt(*self.chars.get(c)?),
+            Event::Key(Key::Ctrl(c)) => Key::Ctrl(*self.chars.get(c)?),
+            _ => return None,
+        };
+        Some(Event::Key(key))
+    }
+}
+
+#[derive(Default, Debug, Clone)]
 pub struct BindingConfig {
     bindings: HashMap<Event, Vec<ContextedAction>>,
+    layout: Layout,
 }
 
 impl From<HashMap<Event, Vec<ContextedAction>>> for BindingConfig {
@@ -130,12 +380,35 @@ impl From<HashMap<Event, Vec<ContextedAction>>> for BindingConfig {
                     }
                 })
                 .collect(),
+            layout: Layout::default(),
         }
     }
 }
 
+/// State shared with the input events task
+pub struct Input {
+    pub context: Arc<Mutex<Context>>,
+    pub mode: Arc<Mutex<Mode>>,
+    pub binding: Arc<Mutex<BindingConfig>>,
+}
+
 impl BindingConfig {
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+    }
+
+    /// Adds the binding with a priority over the existing ones of the same context
+    pub fn bind(&mut self, event: Event, action: ContextedAction) {
+        let actions = self.bindings.entry(event).or_default();
+        actions.insert(0, action);
+        actions.sort_by_key(|v| v.context);
+        actions.dedup_by_key(|v| v.context);
+    }
+
     fn action(&self, context: Context, event: &Event) -> Option<Action> {
+        let translated = self.layout.translate(event);
+        let event = translated.as_ref().unwrap_or(event);
+
         self.bindings
             .get(event)
             .and_then(|actions| {
@@ -155,37 +428,98 @@ impl BindingConfig {
             return None;
         };
 
-        match event {
-            Key::Up => Some(Action::PointerUp),
-            Key::Down => Some(Action::PointerDown),
-            Key::Right => Some(Action::NextTrack),
-            Key::Left => Some(Action::PrevTrack),
-            Key::Delete | Key::Ctrl('c') => Some(Action::Quit),
-            Key::Ctrl('p') => Some(Action::FlipPause),
-            Key::Char(']') => Some(Action::Forward5),
-            Key::Char('[') => Some(Action::Backward5),
-            Key::Ctrl('r') => Some(Action::Refresh),
-            Key::Ctrl('s') => Some(Action::Stop),
-            Key::Ctrl('a') => Some(Action::AddAll),
-            Key::Alt('p') => Some(Action::ShowPlaylist),
-            Key::Alt('a') => Some(Action::SwitchToAlbums),
-            Key::Alt('t') => Some(Action::SwitchToTracks),
-            Key::Alt('s') => Some(Action::SwitchToArtists),
-            Key::Alt('\n') | Key::Alt('\r') => Some(Action::Search),
-            Key::Char('\n') | Key::Char('\r') => Some(Action::Select),
-            Key::Char('\t') => Some(Action::SwitchView),
-            Key::Char(c) => Some(Action::Char(*c)),
-            Key::Backspace => Some(Action::Backspace),
-            Key::Alt('\x1b') => Some(Action::PrevView),
+        DEFAULT_KEYS
+            .iter()
+            .find(|(key, _)| key == event)
+            .map(|(_, action)| *action)
+            .or(match event {
+                Key::Char(c) => Some(Action::Char(*c)),
+                _ => None,
+            })
+    }
+
+    fn prompt_action(event: &Event) -> Option<Action> {
+        let event = if let Event::Key(event) = event {
+            event
+        } else {
+            return None;
+        };
+
+        PROMPT_KEYS
+            .iter()
+            .find(|(key, _)| key == event)
+            .map(|(_, action)| *action)
+            .or(match event {
+                Key::Char(c) => Some(Action::Char(*c)),
+                _ => None,
+            })
+    }
+
+    fn insert_action(&self, event: &Event) -> Option<Action> {
+        let translated = self.layout.translate(event);
+        let event = translated.as_ref().unwrap_or(event);
+
+        let action = INSERT_KEYS
+            .iter()
+            .find(|(key, _)| Event::Key(*key) == *event)
+            .map(|(_, action)| *action);
+
+        // printable chars are always typed, even the ones bound to actions like ':'
+        action.or(match event {
+            Event::Key(Key::Char(c)) if !c.is_control() => Some(Action::Char(*c)),
             _ => None,
+        })
+    }
+
+    /// Action of the event at the input mode
+    fn mode_action(&self, mode: Mode, context: Context, event: &Event) -> Option<Action> {
+        match mode {
+            Mode::Normal => self.action(context, event),
+            Mode::Prompt => BindingConfig::prompt_action(event),
+            Mode::Filtered => {
+                BindingConfig::filtered_action(event).or_else(|| self.action(context, event))
+            }
+            Mode::Insert => self
+                .insert_action(event)
+                .or_else(|| self.action(context, event)),
         }
     }
 
-    pub fn actions(self) -> (mpsc::UnboundedReceiver<Action>, Arc<Mutex<Context>>) {
+    fn filtered_action(event: &Event) -> Option<Action> {
+        FILTERED_KEYS
+            .iter()
+            .find(|(key, _)| Event::Key(*key) == *event)
+            .map(|(_, action)| *action)
+    }
+
+    /// Events which trigger the action at the mode and context, sorted by their names
+    pub fn events(&self, mode: Mode, context: Context, action: Action) -> Vec<Event> {
+        let defaults = [DEFAULT_KEYS, INSERT_KEYS, FILTERED_KEYS, PROMPT_KEYS]
+            .iter()
+            .flat_map(|keys| keys.iter())
+            .map(|(key, _)| Event::Key(*key));
+
+        let mut events: Vec<Event> = vec![];
+        for event in self.bindings.keys().cloned().chain(defaults) {
+            if !events.contains(&event) && self.mode_action(mode, context, &event) == Some(action) {
+                events.push(event);
+            }
+        }
+        events.sort_by_key(event_name);
+        events
+    }
+
+    pub fn actions(self) -> (mpsc::UnboundedReceiver<Action>, Input) {
         let (mut action_tx, action_rx) = mpsc::unbounded();
-        let context = Arc::new(Mutex::new(Context::search()));
+        let input = Input {
+            context: Arc::new(Mutex::new(Context::search())),
+            mode: Arc::new(Mutex::new(Mode::Normal)),
+            binding: Arc::new(Mutex::new(self)),
+        };
 
-        let current_context = context.clone();
+        let current_context = input.context.clone();
+        let current_mode = input.mode.clone();
+        let binding = input.binding.clone();
 
         tokio::spawn(async move {
             let mut stdin = tokio::io::stdin();
@@ -196,7 +530,14 @@ impl BindingConfig {
                 match event {
                     Ok(event) => {
                         let current_context = *current_context.lock().unwrap();
-                        if let Some(action) = self.action(current_context, &event) {
+                        let mode = *current_mode.lock().unwrap();
+                        let action =
+                            binding
+                                .lock()
+                                .unwrap()
+                                .mode_action(mode, current_context, &event);
+
+                        if let Some(action) = action {
                             if let Err(err) = action_tx.send(action).await {
                                 log::warn!("events ended due to closed rx channel {}", err);
                                 break;
@@ -209,7 +550,7 @@ impl BindingConfig {
                 };
             }
         });
-        (action_rx, context)
+        (action_rx, input)
     }
 }
 
@@ -302,4 +643,108 @@ mod tests {
             TestResult::error("item not found")
         }
     }
+
+    #[test]
+    fn test_layout_translation() {
+        let mut config = BindingConfig::default();
+        config.set_layout(Layout::jcuken());
+
+        let alt = Event::Key(Key::Alt('ф'));
+        assert_eq!(
+            config.action(Context::search(), &alt),
+            Some(Action::SwitchToAlbums)
+        );
+
+        let plain = Event::Key(Key::Char('х'));
+        assert_eq!(
+            config.action(Context::search(), &plain),
+            Some(Action::Char('х'))
+        );
+    }
+
+    #[test]
+    fn test_reverse_lookup() {
+        let event = Event::Key(Key::Char('+'));
+        let config: BindingConfig = vec![(
+            event.clone(),
+            vec![ContextedAction {
+                context: Context::playlist(),
+                action: Action::NextTrack,
+            }],
+        )]
+        .into_iter()
+        .collect::<HashMap<_, _>>()
+        .into();
+
+        let events = config.events(Mode::Normal, Context::playlist(), Action::NextTrack);
+        assert_eq!(events, vec![event, Event::Key(Key::Right)]);
+
+        let events = config.events(Mode::Normal, Context::search(), Action::NextTrack);
+        assert_eq!(events, vec![Event::Key(Key::Right)]);
+
+        let events = config.events(Mode::Filtered, Context::search(), Action::NextMatch);
+        assert_eq!(events, vec![Event::Key(Key::Char('n'))]);
+        let events = config.events(Mode::Insert, Context::search(), Action::HistoryPrev);
+        assert_eq!(events, vec![Event::Key(Key::Up)]);
+        let events = config.events(Mode::Prompt, Context::search(), Action::Complete);
+        assert_eq!(events, vec![Event::Key(Key::Char('\t'))]);
+        let events = config.events(Mode::Normal, Context::search(), Action::Select);
+        assert_eq!(
+            events,
+            vec![Event::Key(Key::Char('\n')), Event::Key(Key::Char('\r'))]
+        );
+    }
+
+    #[test]
+    fn test_runtime_binding() {
+        let event = Event::Key(Key::Char('+'));
+        let mut config = BindingConfig::default();
+        config.bind(
+            event.clone(),
+            ContextedAction {
+                context: Context::all(),
+                action: Action::NextTrack,
+            },
+        );
+        config.bind(
+            event.clone(),
+            ContextedAction {
+                context: Context::all(),
+                action: Action::PrevTrack,
+            },
+        );
+        assert_eq!(
+            config.action(Context::playlist(), &event),
+            Some(Action::PrevTrack)
+        );
+    }
+
+    #[test]
+    fn test_insert_typing() {
+        let config = BindingConfig::default();
+        for c in "a:b/c".chars() {
+            let event = Event::Key(Key::Char(c));
+            assert_eq!(
+                config.mode_action(Mode::Insert, Context::search(), &event),
+                Some(Action::Char(c))
+            );
+        }
+
+        let colon = Event::Key(Key::Char(':'));
+        assert_eq!(
+            config.mode_action(Mode::Normal, Context::search(), &colon),
+            Some(Action::CommandLine)
+        );
+        let enter = Event::Key(Key::Char('\n'));
+        assert_eq!(
+            config.mode_action(Mode::Insert, Context::search(), &enter),
+            Some(Action::Select)
+        );
+    }
+
+    #[test]
+    fn test_jcuken_is_complete() {
+        assert_eq!(JCUKEN_LOWER.chars().count(), QWERTY_LOWER.chars().count());
+        assert_eq!(JCUKEN_UPPER.chars().count(), QWERTY_UPPER.chars().count());
+    }
 }
@@ -1,15 +1,25 @@
 use std::error::Error;
 use std::fs::File;
+use std::sync::Arc;
 
 use flexi_logger::Logger;
 
 mod app;
+mod auth;
+mod cache;
+mod cmdline;
 mod config;
 mod draw;
+mod edit;
+mod export;
+mod filter;
+mod history;
 mod input;
+mod jobs;
 mod key;
 mod logger;
 mod meta;
+mod offline;
 mod player;
 mod providers;
 mod view;
@@ -41,12 +51,35 @@ async fn main() -> Result<(), Box<dyn Error>> {
         .transpose()?
         .unwrap_or_else(Config::default);
 
-    let provider = providers::Provider::new();
+    // a yandex music link to open at the start, e.g. `https://music.yandex.ru/album/4766`
+    let link = std::env::args()
+        .nth(1)
+        .map(|arg| {
+            providers::Link::parse(&arg).ok_or_else(|| format!("not a yandex music link: {}", arg))
+        })
+        .transpose()?;
+
+    let provider = providers::Provider::new(&config.provider, &config.cache);
+    if let Some(credentials) = auth::load() {
+        provider.login(credentials);
+    }
 
-    let (player, chan) = player::Player::new();
+    let tracks = Arc::new(offline::TrackCache::open(&config.offline));
+    let (player, chan, url_requests, station_requests) = player::Player::new(tracks.clone());
     let (state, _) = player.start_worker();
+    tokio::spawn(player::resolve_urls(
+        provider.clone(),
+        url_requests,
+        chan.clone(),
+        config.provider.concurrency,
+    ));
+    tokio::spawn(player::serve_stations(
+        provider.clone(),
+        station_requests,
+        chan.clone(),
+    ));
 
-    let app = app::App::create(config, provider, chan, state)?;
+    let app = app::App::create(config, provider, tracks, chan, state, link)?;
     log::info!("Spinning up a fancy UI");
     app.run().await?;
 
@@ -1,46 +1,328 @@
+use std::fmt;
 use std::sync::Arc;
+use std::time::Duration;
 
-#[derive(Debug, Clone)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, snafu::Snafu)]
+pub enum Error {
+    #[snafu(display("{} is not a yandex music url of {}", url, kind))]
+    IncorrectUrl { url: String, kind: &'static str },
+}
+
+/// Catalogue, which issued the id, the ids of different catalogues might clash
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Namespace {
+    Yandex,
+}
+
+/// Number following the `kind` segment of the path, e.g. `4766` at `/album/4766/track/57703`.
+/// Absolute urls must point to yandex music.
+fn path_id(url: &str, kind: &'static str) -> Result<u32, Error> {
+    let path = match url.find("://") {
+        Some(pos) => {
+            let rest = &url[pos + 3..];
+            let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+            if !host.starts_with("music.yandex.") {
+                return IncorrectUrl { url, kind }.fail();
+            }
+            path
+        }
+        None => url,
+    };
+    let path = path.split(['?', '#']).next().unwrap_or("");
+    let mut segments = path.split('/');
+    segments
+        .find(|segment| *segment == kind)
+        .and_then(|_| segments.next())
+        .and_then(|id| id.parse().ok())
+        .ok_or_else(|| Error::IncorrectUrl {
+            url: url.to_string(),
+            kind,
+        })
+}
+
+macro_rules! id_type {
+    ($name:ident, $kind:literal) => {
+        /// Id of the catalogue item, it is unique within the namespace only
+        #[derive(
+            Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+        )]
+        pub struct $name {
+            pub namespace: Namespace,
+            pub id: u32,
+        }
+
+        impl $name {
+            pub fn yandex(id: u32) -> Self {
+                Self {
+                    namespace: Namespace::Yandex,
+                    id,
+                }
+            }
+
+            /// Path of the page, e.g. `/album/4766`
+            pub fn path(&self) -> String {
+                format!("/{}/{}", $kind, self.id)
+            }
+
+            /// Parses either the path of the page or the full link
+            pub fn from_url(url: &str) -> Result<Self, Error> {
+                path_id(url, $kind).map(Self::yandex)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.id)
+            }
+        }
+    };
+}
+
+id_type!(ArtistId, "artist");
+id_type!(AlbumId, "album");
+id_type!(TrackId, "track");
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Artist {
-    pub url: String,
+    pub id: ArtistId,
     pub name: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Artists {
     pub artists: Vec<Artist>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Album {
-    pub url: String,
+    pub id: AlbumId,
     pub title: String,
     pub artists: Vec<Artist>,
     pub year: u16,
     pub version: Option<String>,
+    /// Absent at the scraped pages
+    pub track_count: Option<u32>,
+    pub genre: Option<String>,
+    pub label: Option<String>,
+    pub cover_url: Option<String>,
 }
 
 impl Album {
-    #[allow(unused)]
-    pub fn id(&self) -> u32 {
-        self.url.split('/').nth(1).unwrap().parse().unwrap()
+    pub fn new(id: AlbumId, title: String) -> Self {
+        Self {
+            id,
+            title,
+            artists: vec![],
+            year: 0,
+            version: None,
+            track_count: None,
+            genre: None,
+            label: None,
+            cover_url: None,
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Albums {
     pub albums: Vec<Album>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Track {
-    pub album_id: u32,
-    pub track_id: u32,
+    pub album_id: AlbumId,
+    pub track_id: TrackId,
     pub name: String,
     pub artists: Arc<Vec<Artist>>,
+    pub duration: Option<Duration>,
+    pub album_title: Option<String>,
+    /// Disc and position at the disc, both start from 1
+    pub disc: Option<u16>,
+    pub number: Option<u16>,
+    pub explicit: bool,
+    /// Unavailable tracks are listed, but have no streams
+    pub available: bool,
 }
 
-#[derive(Debug)]
+impl Track {
+    pub fn new(album_id: AlbumId, track_id: TrackId, name: String, artists: Vec<Artist>) -> Self {
+        Self {
+            album_id,
+            track_id,
+            name,
+            artists: Arc::new(artists),
+            duration: None,
+            album_title: None,
+            disc: None,
+            number: None,
+            explicit: false,
+            available: true,
+        }
+    }
+
+    /// Lists the artist first, unless the track already has it
+    pub fn with_artist(mut self, artist: &Artist) -> Self {
+        if !self.artists.iter().any(|x| x.id == artist.id) {
+            Arc::make_mut(&mut self.artists).insert(0, artist.clone());
+        }
+        self
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Tracks {
     pub tracks: Vec<Track>,
 }
+
+/// Account of the logged in user, its library is requested by the uid
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    pub uid: u64,
+    pub login: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Playlist {
+    /// `/users/<owner>/playlists/<kind>`
+    pub url: String,
+    pub owner: String,
+    pub title: String,
+    pub track_count: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Playlists {
+    pub playlists: Vec<Playlist>,
+}
+
+/// Item, which might be liked, i.e. added to the library of the user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Likeable {
+    Track(Track),
+    Album(Album),
+    Artist(Artist),
+}
+
+impl Likeable {
+    pub fn name(&self) -> &str {
+        match self {
+            Likeable::Track(track) => &track.name,
+            Likeable::Album(album) => &album.title,
+            Likeable::Artist(artist) => &artist.name,
+        }
+    }
+}
+
+/// Radio station, which plays an endless sequence of tracks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Station {
+    /// `<type>:<tag>`, e.g. `artist:79215` or `genre:rock`
+    pub id: String,
+    pub name: String,
+}
+
+impl Station {
+    /// Station of the recommendations for the user
+    pub fn my_wave() -> Self {
+        Self {
+            id: "user:onyourwave".to_string(),
+            name: "my wave".to_string(),
+        }
+    }
+
+    pub fn of_artist(artist: &Artist) -> Self {
+        Self {
+            id: format!("artist:{}", artist.id),
+            name: artist.name.clone(),
+        }
+    }
+
+    pub fn of_track(track: &Track) -> Self {
+        Self {
+            id: format!("track:{}", track.track_id),
+            name: track.name.clone(),
+        }
+    }
+
+    pub fn of_genre(genre: &str) -> Self {
+        Self {
+            id: format!("genre:{}", genre),
+            name: genre.to_string(),
+        }
+    }
+}
+
+/// Tracks of a station, the feedback on them is sent with the batch id
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StationTracks {
+    pub batch_id: String,
+    pub tracks: Vec<Track>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Genre {
+    /// Tag of the genre, e.g. `rock`
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Genres {
+    pub genres: Vec<Genre>,
+}
+
+/// Overview of the artist with the discography split into sections
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtistPage {
+    pub artist: Artist,
+    pub bio: Option<String>,
+    pub genres: Vec<String>,
+    pub popular_tracks: Vec<Track>,
+    pub albums: Vec<Album>,
+    pub singles: Vec<Album>,
+    pub compilations: Vec<Album>,
+    /// Albums of other artists, which the artist takes part in
+    pub appearances: Vec<Album>,
+    pub similar: Vec<Artist>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ids() {
+        let url = "https://music.yandex.ru/album/4766/track/57703?from=search";
+        assert_eq!(AlbumId::from_url(url).unwrap(), AlbumId::yandex(4766));
+        assert_eq!(TrackId::from_url(url).unwrap(), TrackId::yandex(57703));
+        assert_eq!(ArtistId::from_url("/artist/79215").unwrap().id, 79215);
+        assert!(ArtistId::from_url("/album/4766").is_err());
+        assert!(AlbumId::from_url("https://example.com/album/4766").is_err());
+        assert!(AlbumId::from_url("/album/latest").is_err());
+    }
+
+    #[test]
+    fn test_serde_roundtrip() {
+        let artist = Artist {
+            id: ArtistId::yandex(79215),
+            name: "Pink Floyd".to_string(),
+        };
+        let mut track = Track::new(
+            AlbumId::yandex(4766),
+            TrackId::yandex(57703),
+            "Time".to_string(),
+            vec![artist],
+        );
+        track.duration = Some(Duration::from_secs(413));
+        let json = serde_json::to_string(&track).unwrap();
+        let parsed: Track = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.duration, track.duration);
+        assert_eq!(parsed.artists[0].name, "Pink Floyd");
+
+        let json = serde_json::to_string(&AlbumId::yandex(4766)).unwrap();
+        assert_eq!(json, r#"{"namespace":"yandex","id":4766}"#);
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::config::OfflineConfig;
+use crate::meta::TrackId;
+
+// This file contains a cache of the downloaded tracks, so that they are played with no network
+
+/// Downloaded tracks stored by their ids, the least recently played ones are removed when the
+/// size limit is hit
+#[derive(Debug)]
+pub struct TrackCache {
+    dir: Option<PathBuf>,
+    max_size: u64,
+    /// Sizes of the cached tracks
+    sizes: Mutex<HashMap<TrackId, u64>>,
+    /// Incremented on every change of the cached tracks, so that their ids are copied only then
+    generation: AtomicUsize,
+}
+
+impl TrackCache {
+    /// Cache at `$XDG_CACHE_HOME/rum-player/tracks`
+    pub fn open(config: &OfflineConfig) -> Self {
+        let dir = dirs::cache_dir().map(|dir| dir.join("rum-player").join("tracks"));
+        Self::at(dir, config.max_size)
+    }
+
+    fn at(dir: Option<PathBuf>, max_size: u64) -> Self {
+        let sizes = dir
+            .as_ref()
+            .and_then(|dir| fs::read_dir(dir).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let track_id = track_id(&entry.path())?;
+                Some((track_id, entry.metadata().ok()?.len()))
+            })
+            .collect();
+        Self {
+            dir,
+            max_size,
+            sizes: Mutex::new(sizes),
+            generation: AtomicUsize::new(0),
+        }
+    }
+
+    fn file(&self, track_id: TrackId) -> Option<PathBuf> {
+        Some(self.dir.as_ref()?.join(format!("{}.mp3", track_id)))
+    }
+
+    pub fn contains(&self, track_id: TrackId) -> bool {
+        self.sizes.lock().unwrap().contains_key(&track_id)
+    }
+
+    pub fn ids(&self) -> HashSet<TrackId> {
+        self.sizes.lock().unwrap().keys().copied().collect()
+    }
+
+    pub fn generation(&self) -> usize {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    fn changed(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Path of the cached track, which is marked as recently used
+    pub fn get(&self, track_id: TrackId) -> Option<PathBuf> {
+        if !self.contains(track_id) {
+            return None;
+        }
+        let path = self.file(track_id)?;
+        let touched = fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()));
+        match touched {
+            Ok(()) => Some(path),
+            Err(err) => {
+                log::warn!("cached track {} is unavailable: {}", track_id, err);
+                self.sizes.lock().unwrap().remove(&track_id);
+                self.changed();
+                None
+            }
+        }
+    }
+
+    pub fn put(&self, track_id: TrackId, data: &[u8]) -> io::Result<()> {
+        let path = match self.file(track_id) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // a partially written file should never be played
+        let partial = path.with_extension("part");
+        fs::write(&partial, data)?;
+        fs::rename(&partial, &path)?;
+
+        self.sizes
+            .lock()
+            .unwrap()
+            .insert(track_id, data.len() as u64);
+        let evicted = self.evict();
+        self.changed();
+        evicted
+    }
+
+    /// Removes the least recently used tracks, until the cache fits the size limit
+    fn evict(&self) -> io::Result<()> {
+        let mut sizes = self.sizes.lock().unwrap();
+        let mut size: u64 = sizes.values().sum();
+        if size <= self.max_size {
+            return Ok(());
+        }
+
+        let mut tracks = vec![];
+        let mut missing = vec![];
+        for &track_id in sizes.keys() {
+            if let Some(path) = self.file(track_id) {
+                match fs::metadata(&path).and_then(|metadata| metadata.modified()) {
+                    Ok(modified) => tracks.push((modified, track_id, path)),
+                    Err(err) => {
+                        log::warn!("cached track {} is unavailable: {}", track_id, err);
+                        missing.push(track_id);
+                    }
+                }
+            }
+        }
+        for track_id in missing {
+            size -= sizes.remove(&track_id).unwrap_or(0);
+        }
+        tracks.sort();
+
+        for (_, track_id, path) in tracks {
+            if size <= self.max_size {
+                break;
+            }
+            fs::remove_file(path)?;
+            size -= sizes.remove(&track_id).unwrap_or(0);
+        }
+        Ok(())
+    }
+}
+
+fn track_id(path: &Path) -> Option<TrackId> {
+    if path.extension()? != "mp3" {
+        return None;
+    }
+    path.file_stem()?
+        .to_str()?
+        .parse()
+        .ok()
+        .map(TrackId::yandex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_least_recently_used_are_evicted() {
+        let dir = std::env::temp_dir().join(format!("rum-player-tracks-{}", std::process::id()));
+        let cache = TrackCache::at(Some(dir.clone()), 10);
+
+        cache.put(TrackId::yandex(1), b"first").unwrap();
+        cache.put(TrackId::yandex(2), b"second").unwrap();
+        assert_eq!(cache.ids(), [TrackId::yandex(2)].iter().copied().collect());
+
+        cache.put(TrackId::yandex(3), b"3").unwrap();
+        assert!(cache.get(TrackId::yandex(2)).is_some());
+        cache.put(TrackId::yandex(4), b"four").unwrap();
+        let reopened = TrackCache::at(Some(dir.clone()), 10).ids();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            reopened,
+            [TrackId::yandex(2), TrackId::yandex(4)]
+                .iter()
+                .copied()
+                .collect()
+        );
+        assert_eq!(cache.ids(), reopened);
+    }
+
+    #[test]
+    fn test_missing_tracks_are_dropped() {
+        let dir = std::env::temp_dir().join(format!("rum-player-missing-{}", std::process::id()));
+        let cache = TrackCache::at(Some(dir.clone()), 10);
+
+        cache.put(TrackId::yandex(1), b"first").unwrap();
+        let generation = cache.generation();
+        fs::remove_file(dir.join("1.mp3")).unwrap();
+        cache.put(TrackId::yandex(2), b"second").unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(cache.ids(), [TrackId::yandex(2)].iter().copied().collect());
+        assert!(cache.generation() > generation);
+    }
+}
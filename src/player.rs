@@ -1,9 +1,22 @@
+use std::collections::HashMap;
+use std::result::Result as StdResult;
 use std::sync::mpsc::{self, TryRecvError};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+use futures::channel::mpsc as async_mpsc;
+use futures::prelude::*;
 use mpv::{MpvHandler, MpvHandlerBuilder, Result};
 
-use crate::meta::Track;
+use crate::meta::{Station, StationTracks, Track, TrackId};
+use crate::offline::TrackCache;
+use crate::providers::{self, Feedback, Provider};
+
+/// Signed stream urls expire, so the older ones are resolved again
+const URL_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// More tracks of the station are requested, when fewer are left to play
+const STATION_REFILL: usize = 3;
 
 struct MediaWorker {
     handler: MpvHandler,
@@ -19,8 +32,11 @@ impl MediaWorker {
         })
     }
 
-    fn loadfile(&mut self, url: &str) -> Result<()> {
-        self.handler.command(&["loadfile", &url, "append-play"])?;
+    /// Replaces the played file, it is either a stream url or a path of the downloaded track
+    fn loadfile(&mut self, location: &str) -> Result<()> {
+        self.handler.command(&["loadfile", location, "replace"])?;
+        self.is_paused = false;
+        self.handler.set_property("pause", false)?;
         Ok(())
     }
 
@@ -29,16 +45,6 @@ impl MediaWorker {
         Ok(())
     }
 
-    fn next(&mut self) -> Result<()> {
-        self.handler.command(&["playlist-next"])?;
-        Ok(())
-    }
-
-    fn prev(&mut self) -> Result<()> {
-        self.handler.command(&["playlist-prev"])?;
-        Ok(())
-    }
-
     fn flip_pause(&mut self) -> Result<()> {
         self.is_paused ^= true;
         self.handler.set_property("pause", self.is_paused)?;
@@ -51,36 +57,67 @@ impl MediaWorker {
         Ok(())
     }
 
-    fn playlist_pos(&self) -> Result<usize> {
-        let pos: i64 = self.handler.get_property("playlist-pos")?;
-        Ok(pos as usize)
+    fn set_volume(&mut self, volume: u8) -> Result<()> {
+        self.handler.set_property("volume", i64::from(volume))?;
+        Ok(())
     }
 
-    fn poll_events(&mut self) -> Result<bool> {
+    /// Waits for the events a bit, returns the end of the played file if any
+    fn poll_events(&mut self) -> Option<FileEnd> {
         while let Some(ev) = self.handler.wait_event(0.1) {
             match ev {
-                mpv::Event::Shutdown | mpv::Event::Idle => {
-                    return Ok(false);
+                mpv::Event::EndFile(Ok(mpv::EndFileReason::MPV_END_FILE_REASON_EOF)) => {
+                    return Some(FileEnd::Finished)
                 }
+                mpv::Event::EndFile(Err(err)) => return Some(FileEnd::Failed(err)),
                 _ => log::debug!("mpv: {:?}", ev),
             }
         }
-        Ok(true)
+        None
     }
 }
 
+enum FileEnd {
+    Finished,
+    Failed(mpv::Error),
+}
+
+#[derive(Debug)]
 pub enum Command {
-    Enqueue { track: Track, url: String },
+    Enqueue {
+        track: Box<Track>,
+    },
+    /// Stream url of the track, requested by the player itself
+    Resolved {
+        track_id: TrackId,
+        url: StdResult<String, providers::Error>,
+    },
+    /// Replaces the queue with the tracks of the station
+    StartStation(Station),
+    /// Next tracks of the station, requested by the player itself
+    StationTracks {
+        station_id: String,
+        tracks: StdResult<StationTracks, providers::Error>,
+    },
+    /// The track is added to the library, the station is told about it
+    Liked {
+        track_id: TrackId,
+    },
     Stop,
     NextTrack,
     PrevTrack,
     FlipPause,
     Seek(i64),
+    SeekTo(i64),
+    Volume(u8),
 }
 
 pub struct PlayerState {
     playlist: Vec<Track>,
     current_position: usize,
+    station: Option<Station>,
+    /// Playback failures, which are not shown to the user yet
+    failures: Vec<String>,
 }
 
 impl PlayerState {
@@ -88,9 +125,16 @@ impl PlayerState {
         Self {
             playlist: vec![],
             current_position: 0,
+            station: None,
+            failures: vec![],
         }
     }
 
+    /// Station, which the queue is refilled from
+    pub fn station(&self) -> Option<&Station> {
+        self.station.as_ref()
+    }
+
     pub fn playlist(&self) -> impl Iterator<Item = &'_ Track> {
         self.playlist.iter()
     }
@@ -98,20 +142,485 @@ impl PlayerState {
     pub fn current(&self) -> usize {
         self.current_position
     }
+
+    pub fn has_failures(&self) -> bool {
+        !self.failures.is_empty()
+    }
+
+    pub fn take_failures(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.failures)
+    }
 }
 
 pub type State = Arc<Mutex<PlayerState>>;
 
+/// Tracks, whose stream urls should be resolved
+pub type UrlRequests = async_mpsc::UnboundedReceiver<Track>;
+
+/// Resolves the requested urls and sends them back to the player
+pub async fn resolve_urls(
+    provider: Provider,
+    requests: UrlRequests,
+    commands: mpsc::Sender<Command>,
+    concurrency: usize,
+) {
+    requests
+        .for_each_concurrent(concurrency, |track| {
+            let provider = provider.clone();
+            let commands = commands.clone();
+            async move {
+                let url = provider.get_track_url(&track).await;
+                let track_id = track.track_id;
+                // the player is finished otherwise
+                let _ = commands.send(Command::Resolved { track_id, url });
+            }
+        })
+        .await
+}
+
+/// Requests to the station api, sent by the player
+#[derive(Debug)]
+pub enum StationRequest {
+    Tracks {
+        station: Station,
+        last: Option<TrackId>,
+    },
+    Feedback {
+        station: Station,
+        batch_id: String,
+        feedback: Feedback,
+    },
+}
+
+pub type StationRequests = async_mpsc::UnboundedReceiver<StationRequest>;
+
+/// Requests the station tracks and sends them back to the player, the feedback is sent as is
+pub async fn serve_stations(
+    provider: Provider,
+    requests: StationRequests,
+    commands: mpsc::Sender<Command>,
+) {
+    requests
+        .for_each(|request| {
+            let provider = provider.clone();
+            let commands = commands.clone();
+            async move {
+                match request {
+                    StationRequest::Tracks { station, last } => {
+                        let tracks = provider.station_tracks(&station, last).await;
+                        // the player is finished otherwise
+                        let _ = commands.send(Command::StationTracks {
+                            station_id: station.id,
+                            tracks,
+                        });
+                    }
+                    StationRequest::Feedback {
+                        station,
+                        batch_id,
+                        feedback,
+                    } => {
+                        let sent = provider
+                            .station_feedback(&station, &batch_id, feedback)
+                            .await;
+                        if let Err(err) = sent {
+                            log::warn!("cannot send feedback to {}: {}", station.name, err);
+                        }
+                    }
+                }
+            }
+        })
+        .await
+}
+
+/// Station, which is played at the moment
+struct Radio {
+    station: Station,
+    /// Batch of the last received tracks, there is none until the first one is received
+    batch_id: Option<String>,
+    loading: bool,
+}
+
+/// Player queue, the stream urls are resolved just before the tracks are played
+struct Queue {
+    state: State,
+    requests: async_mpsc::UnboundedSender<Track>,
+    stations: async_mpsc::UnboundedSender<StationRequest>,
+    radio: Option<Radio>,
+    /// Start of the played track
+    played_since: Option<Instant>,
+    /// Time the played track was paused for, the current pause is not counted until it ends
+    paused: Duration,
+    paused_since: Option<Instant>,
+    urls: HashMap<TrackId, (String, Instant)>,
+    /// Downloaded tracks are played from disk, their urls are not resolved
+    tracks: Arc<TrackCache>,
+    /// Position of the track loaded into mpv
+    playing: Option<usize>,
+    /// Position of the track waiting for its url
+    waiting: Option<usize>,
+    /// Position of the track, whose url is already resolved again after a failure
+    retried: Option<usize>,
+}
+
+impl Queue {
+    fn track(&self, position: usize) -> Option<Track> {
+        self.state.lock().unwrap().playlist.get(position).cloned()
+    }
+
+    fn fresh_url(&self, track: &Track) -> Option<&str> {
+        self.urls
+            .get(&track.track_id)
+            .filter(|(_, resolved)| resolved.elapsed() < URL_TTL)
+            .map(|(url, _)| url.as_str())
+    }
+
+    /// Logs the failure and passes it to the UI
+    fn fail(&self, failure: String) {
+        log::error!("{}", failure);
+        self.state.lock().unwrap().failures.push(failure);
+    }
+
+    fn request(&mut self, track: Track) {
+        if let Err(err) = self.requests.unbounded_send(track) {
+            log::error!("url resolver is stopped: {}", err);
+        }
+    }
+
+    /// Plays the track at the position, or waits for its url
+    fn play(&mut self, worker: &mut MediaWorker, position: usize) {
+        let track = match self.track(position) {
+            Some(track) => track,
+            None => {
+                self.playing = None;
+                self.waiting = None;
+                self.refill();
+                return;
+            }
+        };
+        self.state.lock().unwrap().current_position = position;
+        if self.retried != Some(position) {
+            self.retried = None;
+        }
+
+        let cached = self.tracks.get(track.track_id);
+        let location = match (cached, self.fresh_url(&track)) {
+            (Some(path), _) => path.to_string_lossy().into_owned(),
+            (None, Some(url)) => url.to_string(),
+            (None, None) => {
+                if self.playing.take().is_some() {
+                    if let Err(err) = worker.stop() {
+                        log::error!("cannot stop the track: {}", err);
+                    }
+                }
+                self.waiting = Some(position);
+                self.request(track);
+                return;
+            }
+        };
+        self.waiting = None;
+        if let Err(err) = worker.loadfile(&location) {
+            log::debug!("failed location of {}: {}", track.name, location);
+            self.fail(format!("cannot load {}: {}", track.name, err));
+            self.playing = None;
+            return;
+        }
+        self.playing = Some(position);
+        self.played_since = Some(Instant::now());
+        self.paused = Duration::default();
+        self.paused_since = None;
+
+        if let Some(next) = self.track(position + 1) {
+            if !self.tracks.contains(next.track_id) && self.fresh_url(&next).is_none() {
+                self.request(next);
+            }
+        }
+        self.refill();
+    }
+
+    fn send_station(&self, request: StationRequest) {
+        if let Err(err) = self.stations.unbounded_send(request) {
+            log::error!("station requests are stopped: {}", err);
+        }
+    }
+
+    fn start_station(&mut self, worker: &mut MediaWorker, station: Station) {
+        if let Err(err) = worker.stop() {
+            log::error!("cannot stop the track: {}", err);
+        }
+        self.clear();
+        self.state.lock().unwrap().station = Some(station.clone());
+        self.radio = Some(Radio {
+            station,
+            batch_id: None,
+            loading: false,
+        });
+        self.refill();
+    }
+
+    /// Requests more tracks of the station, if only a few are left to play
+    fn refill(&mut self) {
+        let (left, last) = {
+            let state = self.state.lock().unwrap();
+            let left = state
+                .playlist
+                .len()
+                .saturating_sub(state.current_position + 1);
+            (left, state.playlist.last().map(|t| t.track_id))
+        };
+        let radio = match self.radio {
+            Some(ref mut radio) if !radio.loading => radio,
+            _ => return,
+        };
+        if left >= STATION_REFILL {
+            return;
+        }
+
+        radio.loading = true;
+        let station = radio.station.clone();
+        self.send_station(StationRequest::Tracks { station, last });
+    }
+
+    fn station_tracks(
+        &mut self,
+        worker: &mut MediaWorker,
+        station_id: String,
+        tracks: StdResult<StationTracks, providers::Error>,
+    ) {
+        let (name, started) = match self.radio {
+            Some(ref radio) if radio.station.id == station_id => {
+                (radio.station.name.clone(), radio.batch_id.is_some())
+            }
+            // the station is stopped or replaced meanwhile
+            _ => return,
+        };
+        match tracks {
+            Ok(StationTracks { batch_id, tracks }) => {
+                if let Some(radio) = self.radio.as_mut() {
+                    radio.batch_id = Some(batch_id);
+                }
+                if !started {
+                    self.feedback(Feedback::RadioStarted);
+                }
+                for track in tracks {
+                    self.enqueue(worker, track);
+                }
+            }
+            Err(err) => {
+                self.fail(format!("cannot get tracks of {}: {}", name, err));
+                // the station is not started at all, so its queue is never refilled
+                if !started {
+                    self.radio = None;
+                    self.state.lock().unwrap().station = None;
+                }
+            }
+        }
+        // the tracks are requested again, once the next track is played
+        if let Some(radio) = self.radio.as_mut() {
+            radio.loading = false;
+        }
+    }
+
+    /// Sends the feedback to the played station, if there is one
+    fn feedback(&self, feedback: Feedback) {
+        if let Some(Radio {
+            ref station,
+            batch_id: Some(ref batch_id),
+            ..
+        }) = self.radio
+        {
+            self.send_station(StationRequest::Feedback {
+                station: station.clone(),
+                batch_id: batch_id.clone(),
+                feedback,
+            });
+        }
+    }
+
+    /// Time the track is played for, paused time is not counted
+    fn played(&self) -> Duration {
+        let paused = self.paused
+            + self
+                .paused_since
+                .map(|since| since.elapsed())
+                .unwrap_or_default();
+        self.played_since
+            .and_then(|since| since.elapsed().checked_sub(paused))
+            .unwrap_or_default()
+    }
+
+    fn flip_pause(&mut self, worker: &mut MediaWorker) {
+        if let Err(err) = worker.flip_pause() {
+            log::error!("cannot pause/unpause track: {}", err);
+            return;
+        }
+        if worker.is_paused {
+            self.paused_since = Some(Instant::now());
+        } else if let Some(since) = self.paused_since.take() {
+            self.paused += since.elapsed();
+        }
+    }
+
+    fn enqueue(&mut self, worker: &mut MediaWorker, track: Track) {
+        let position = {
+            let mut state = self.state.lock().unwrap();
+            state.playlist.push(track);
+            state.playlist.len() - 1
+        };
+        match (self.playing, self.waiting) {
+            (None, None) => self.play(worker, position),
+            // the next track is prefetched
+            (Some(current), _) if current + 1 == position => {
+                if let Some(track) = self.track(position) {
+                    if !self.tracks.contains(track.track_id) {
+                        self.request(track);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn resolved(
+        &mut self,
+        worker: &mut MediaWorker,
+        track_id: TrackId,
+        url: StdResult<String, providers::Error>,
+    ) {
+        let waiting = self
+            .waiting
+            .filter(|pos| self.track(*pos).map(|t| t.track_id) == Some(track_id));
+
+        match url {
+            Ok(url) => {
+                self.urls.insert(track_id, (url, Instant::now()));
+                if let Some(position) = waiting {
+                    self.play(worker, position);
+                }
+            }
+            Err(err) => match waiting.and_then(|pos| self.track(pos).map(|track| (pos, track))) {
+                Some((position, track)) if self.retried != Some(position) => {
+                    log::warn!(
+                        "cannot get url of {}, resolving it again: {}",
+                        track.name,
+                        err
+                    );
+                    self.retried = Some(position);
+                    self.request(track);
+                }
+                Some((position, track)) => {
+                    self.fail(format!("cannot get url of {}: {}", track.name, err));
+                    self.play(worker, position + 1);
+                }
+                None => log::error!("cannot get url of track {}: {}", track_id, err),
+            },
+        }
+    }
+
+    /// Plays the next track, the failed one is retried once with a newly resolved url
+    fn file_ended(&mut self, worker: &mut MediaWorker, end: FileEnd) {
+        let position = match self.playing {
+            Some(position) => position,
+            None => return,
+        };
+
+        match end {
+            FileEnd::Failed(err) if self.retried != Some(position) => {
+                log::warn!(
+                    "track at {} is failed, resolving it again: {}",
+                    position,
+                    err
+                );
+                if let Some(track) = self.track(position) {
+                    self.urls.remove(&track.track_id);
+                }
+                self.retried = Some(position);
+                self.play(worker, position);
+            }
+            FileEnd::Failed(err) => {
+                let name = self.track(position).map(|track| track.name);
+                self.fail(format!(
+                    "cannot play {}: {}",
+                    name.as_deref().unwrap_or("track"),
+                    err
+                ));
+                self.play(worker, position + 1);
+            }
+            FileEnd::Finished => {
+                if let Some(track) = self.track(position) {
+                    self.feedback(Feedback::TrackFinished {
+                        track_id: track.track_id,
+                        played: self.played(),
+                    });
+                }
+                self.play(worker, position + 1)
+            }
+        }
+    }
+
+    fn current(&self) -> Option<usize> {
+        self.playing.or(self.waiting)
+    }
+
+    /// Plays the track at the position relative to the current one, if there is such a track
+    fn skip(&mut self, worker: &mut MediaWorker, offset: isize) {
+        let len = self.state.lock().unwrap().playlist.len() as isize;
+        if let Some(current) = self.current() {
+            let position = current as isize + offset;
+            if 0 <= position && position < len {
+                if let (Some(track), true) = (self.track(current), offset > 0) {
+                    self.feedback(Feedback::Skip {
+                        track_id: track.track_id,
+                        played: self.played(),
+                    });
+                }
+                self.play(worker, position as usize);
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        state.playlist.clear();
+        state.current_position = 0;
+        state.station = None;
+        self.radio = None;
+        self.played_since = None;
+        self.paused = Duration::default();
+        self.paused_since = None;
+        self.playing = None;
+        self.waiting = None;
+        self.retried = None;
+    }
+}
+
 pub struct Player {
     rx: mpsc::Receiver<Command>,
+    requests: async_mpsc::UnboundedSender<Track>,
+    stations: async_mpsc::UnboundedSender<StationRequest>,
+    tracks: Arc<TrackCache>,
     state: State,
 }
 
 impl Player {
-    pub fn new() -> (Self, mpsc::Sender<Command>) {
+    pub fn new(
+        tracks: Arc<TrackCache>,
+    ) -> (Self, mpsc::Sender<Command>, UrlRequests, StationRequests) {
         let (tx, rx) = mpsc::channel();
+        let (requests, requests_rx) = async_mpsc::unbounded();
+        let (stations, stations_rx) = async_mpsc::unbounded();
         let state = Arc::new(Mutex::new(PlayerState::new()));
-        (Self { rx, state }, tx)
+        (
+            Self {
+                rx,
+                requests,
+                stations,
+                tracks,
+                state,
+            },
+            tx,
+            requests_rx,
+            stations_rx,
+        )
     }
 
     pub fn start_worker(self) -> (State, std::thread::JoinHandle<Result<()>>) {
@@ -119,47 +628,57 @@ impl Player {
 
         let handle = std::thread::spawn(move || {
             let mut worker = MediaWorker::new()?;
+            let mut queue = Queue {
+                state: self.state,
+                requests: self.requests,
+                stations: self.stations,
+                radio: None,
+                played_since: None,
+                paused: Duration::default(),
+                paused_since: None,
+                urls: HashMap::new(),
+                tracks: self.tracks,
+                playing: None,
+                waiting: None,
+                retried: None,
+            };
             loop {
-                worker.poll_events()?;
+                if let Some(end) = worker.poll_events() {
+                    queue.file_ended(&mut worker, end);
+                }
                 match self.rx.try_recv() {
-                    Ok(Command::Enqueue { track, url }) => {
-                        if let Err(err) = worker.loadfile(&url) {
-                            log::error!("cannot load {}: {}, url: {}", track.name, err, url);
-                        } else {
-                            self.state.lock().unwrap().playlist.push(track);
-                        }
+                    Ok(Command::Enqueue { track }) => queue.enqueue(&mut worker, *track),
+                    Ok(Command::Resolved { track_id, url }) => {
+                        queue.resolved(&mut worker, track_id, url)
+                    }
+                    Ok(Command::StartStation(station)) => queue.start_station(&mut worker, station),
+                    Ok(Command::StationTracks { station_id, tracks }) => {
+                        queue.station_tracks(&mut worker, station_id, tracks)
                     }
+                    Ok(Command::Liked { track_id }) => queue.feedback(Feedback::Like { track_id }),
                     Ok(Command::Stop) => {
                         if let Err(err) = worker.stop() {
                             log::error!("cannot stop the track: {}", err);
                         } else {
-                            let mut state = self.state.lock().unwrap();
-                            state.playlist.clear();
-                            state.current_position = 0;
+                            queue.clear();
                         }
                     }
-                    Ok(Command::NextTrack) => {
-                        if let Err(err) = worker.next() {
-                            log::error!("cannot switch to next track: {}", err);
-                        } else {
-                            self.state.lock().unwrap().current_position += 1;
-                        }
-                    }
-                    Ok(Command::PrevTrack) => {
-                        if let Err(err) = worker.prev() {
-                            log::error!("cannot switch to previous track: {}", err);
-                        } else {
-                            self.state.lock().unwrap().current_position -= 1;
+                    Ok(Command::NextTrack) => queue.skip(&mut worker, 1),
+                    Ok(Command::PrevTrack) => queue.skip(&mut worker, -1),
+                    Ok(Command::FlipPause) => queue.flip_pause(&mut worker),
+                    Ok(Command::Seek(x)) => {
+                        if let Err(err) = worker.time_seek(|pos| pos + x) {
+                            log::error!("cannot seek time ({} secs): {}", x, err);
                         }
                     }
-                    Ok(Command::FlipPause) => {
-                        if let Err(err) = worker.flip_pause() {
-                            log::error!("cannot pause/unpause track: {}", err);
+                    Ok(Command::SeekTo(x)) => {
+                        if let Err(err) = worker.time_seek(|_| x) {
+                            log::error!("cannot seek time (to {} secs): {}", x, err);
                         }
                     }
-                    Ok(Command::Seek(x)) => {
-                        if let Err(err) = worker.time_seek(|pos| pos + x) {
-                            log::error!("cannot seek time ({} secs): {}", x, err);
+                    Ok(Command::Volume(volume)) => {
+                        if let Err(err) = worker.set_volume(volume) {
+                            log::error!("cannot set volume to {}: {}", volume, err);
                         }
                     }
                     Err(TryRecvError::Empty) => {}
@@ -168,15 +687,6 @@ impl Player {
                         return Ok(());
                     }
                 }
-
-                if let Ok(pos) = worker.playlist_pos() {
-                    let mut state = self.state.lock().unwrap();
-                    state.current_position = pos;
-                } else {
-                    let mut state = self.state.lock().unwrap();
-                    state.playlist.clear();
-                    state.current_position = 0;
-                }
             }
         });
 
@@ -1,16 +1,22 @@
+use std::collections::hash_map::DefaultHasher;
 use std::convert::{TryFrom, TryInto};
+use std::future::Future;
+use std::hash::{Hash, Hasher};
 use std::result::Result as StdResult;
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
-use futures::future::TryFutureExt;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder, StatusCode, Url};
 use snafu::ResultExt;
-use strum_macros::Display;
+use strum_macros::{Display, EnumString};
 use unhtml::FromHtml;
 
+use crate::auth::Credentials;
+use crate::cache::{CacheKind, HttpCache};
+use crate::config::{CacheConfig, ProviderConfig};
 use crate::meta;
 
-const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+mod json;
 
 #[derive(FromHtml)]
 struct ArtistRaw {
@@ -25,7 +31,7 @@ impl TryFrom<ArtistRaw> for meta::Artist {
 
     fn try_from(raw: ArtistRaw) -> StdResult<Self, Self::Error> {
         Ok(Self {
-            url: raw.url.ok_or(())?,
+            id: meta::ArtistId::from_url(&raw.url.ok_or(())?).map_err(drop)?,
             name: raw.name.ok_or(())?,
         })
     }
@@ -69,8 +75,6 @@ impl TryFrom<AlbumRaw> for meta::Album {
 
     fn try_from(raw: AlbumRaw) -> StdResult<Self, Self::Error> {
         Ok(Self {
-            url: raw.url,
-            title: raw.title,
             artists: raw
                 .artists
                 .into_iter()
@@ -82,6 +86,7 @@ impl TryFrom<AlbumRaw> for meta::Album {
                 .parse()
                 .map_err(|_| ())?,
             version: raw.version,
+            ..meta::Album::new(meta::AlbumId::from_url(&raw.url).map_err(drop)?, raw.title)
         })
     }
 }
@@ -105,6 +110,106 @@ impl From<AlbumsRaw> for meta::Albums {
     }
 }
 
+#[derive(FromHtml)]
+struct PlaylistRaw {
+    #[html(selector = "div.playlist__title a", attr = "href")]
+    url: Option<String>,
+    #[html(selector = "div.playlist__title a", attr = "inner")]
+    title: Option<String>,
+    #[html(selector = "div.playlist__counter", attr = "inner")]
+    track_count: Option<String>,
+}
+
+impl TryFrom<PlaylistRaw> for meta::Playlist {
+    type Error = ();
+
+    fn try_from(raw: PlaylistRaw) -> StdResult<Self, Self::Error> {
+        // `/users/music-blog/playlists/1000`
+        let url = raw.url.ok_or(())?;
+        let owner = url.split('/').nth(2).ok_or(())?.to_string();
+        let track_count = raw
+            .track_count
+            .map(|count| {
+                count
+                    .chars()
+                    .filter(char::is_ascii_digit)
+                    .collect::<String>()
+            })
+            .and_then(|count| count.parse().ok())
+            .unwrap_or(0);
+        Ok(Self {
+            url,
+            owner,
+            title: raw.title.ok_or(())?,
+            track_count,
+        })
+    }
+}
+
+#[derive(FromHtml)]
+#[html(selector = "div.centerblock")]
+struct PlaylistsRaw {
+    #[html(selector = "div.playlist")]
+    playlists: Vec<PlaylistRaw>,
+}
+
+impl From<PlaylistsRaw> for meta::Playlists {
+    fn from(raws: PlaylistsRaw) -> Self {
+        Self {
+            playlists: raws
+                .playlists
+                .into_iter()
+                .filter_map(|raw| raw.try_into().ok())
+                .collect(),
+        }
+    }
+}
+
+#[derive(FromHtml)]
+struct GenreRaw {
+    #[html(attr = "href")]
+    url: Option<String>,
+    #[html(attr = "inner")]
+    name: Option<String>,
+}
+
+#[derive(FromHtml)]
+#[html(selector = "div.centerblock")]
+struct GenresRaw {
+    #[html(selector = "a[href^=\"/genre/\"]")]
+    genres: Vec<GenreRaw>,
+}
+
+impl From<GenresRaw> for meta::Genres {
+    fn from(raws: GenresRaw) -> Self {
+        let mut genres: Vec<meta::Genre> = vec![];
+        for raw in raws.genres {
+            // `/genre/rock`, the genres are linked by both the titles and the covers
+            let id = raw.url.as_deref().and_then(|url| url.split('/').nth(2));
+            let name = raw
+                .name
+                .as_deref()
+                .map(str::trim)
+                .filter(|name| !name.is_empty());
+            if let (Some(id), Some(name)) = (id, name) {
+                if !genres.iter().any(|genre| genre.id == id) {
+                    genres.push(meta::Genre {
+                        id: id.to_string(),
+                        name: name.to_string(),
+                    });
+                }
+            }
+        }
+        Self { genres }
+    }
+}
+
+#[derive(FromHtml)]
+struct CoverRaw {
+    #[html(selector = "meta[property=\"og:image\"]", attr = "content")]
+    url: Option<String>,
+}
+
 #[derive(FromHtml)]
 struct TrackRaw {
     #[html(selector = "div.d-track__name a.d-track__title", attr = "href")]
@@ -113,6 +218,17 @@ struct TrackRaw {
     name: Option<String>,
     #[html(selector = "div.d-track__meta span.d-track__artists a")]
     artists: Vec<ArtistRaw>,
+    /// `6:53`
+    #[html(selector = "div.d-track__info span.typo-track", attr = "inner")]
+    duration: Option<String>,
+}
+
+/// Parses `m:ss` or `h:mm:ss`
+fn parse_duration(s: &str) -> Option<Duration> {
+    s.trim()
+        .split(':')
+        .try_fold(0, |secs, part| Some(secs * 60 + part.parse::<u64>().ok()?))
+        .map(Duration::from_secs)
 }
 
 impl TryFrom<TrackRaw> for meta::Track {
@@ -122,14 +238,8 @@ impl TryFrom<TrackRaw> for meta::Track {
         // `/album/4766/track/57703`
         let url = raw.url.ok_or(())?;
         let name = raw.name.ok_or(())?;
-        let mut iter = url.split('/');
-        iter.next();
-        let mut parse_int = move || {
-            iter.next();
-            iter.next().and_then(|val| val.parse().ok()).ok_or(())
-        };
-        let album_id = parse_int()?;
-        let track_id = parse_int()?;
+        let album_id = meta::AlbumId::from_url(&url).map_err(drop)?;
+        let track_id = meta::TrackId::from_url(&url).map_err(drop)?;
 
         let artists = raw
             .artists
@@ -137,12 +247,9 @@ impl TryFrom<TrackRaw> for meta::Track {
             .filter_map(|raw| raw.try_into().ok())
             .collect();
 
-        Ok(Self {
-            album_id,
-            track_id,
-            name,
-            artists: std::sync::Arc::new(artists),
-        })
+        let mut track = meta::Track::new(album_id, track_id, name, artists);
+        track.duration = raw.duration.as_deref().and_then(parse_duration);
+        Ok(track)
     }
 }
 
@@ -199,26 +306,263 @@ struct DownloadInfo {
     s: String,
 }
 
+/// Web page of the track, e.g. `https://music.yandex.ru/album/4766/track/57703`
+pub fn track_page_url(track: &meta::Track) -> String {
+    format!(
+        "{}/album/{}/track/{}",
+        BASE_URL, track.album_id, track.track_id
+    )
+}
+
 #[derive(Debug, snafu::Snafu)]
 pub enum Error {
-    #[snafu(display("http err: {}", source))]
+    #[snafu(display("cannot reach {}: {}, check the connection", url, source))]
     HttpError { url: String, source: reqwest::Error },
-    #[snafu(display("html error: {}", source))]
-    HtmlError { source: unhtml::Error },
-    #[snafu(display("xml error: {}", source))]
+    #[snafu(display("no answer from {} in time, check the connection", url))]
+    Timeout {
+        url: String,
+        source: tokio::time::Elapsed,
+    },
+    #[snafu(display("nothing is found at {}, it might be removed", url))]
+    NotFound { url: String },
+    #[snafu(display("too many requests, wait a bit and try again"))]
+    RateLimited { url: String },
+    #[snafu(display("yandex asks to solve a captcha at {}", url))]
+    Captcha { url: String },
+    #[snafu(display("the library needs a login with a token, use `:login token <token>`"))]
+    NotLoggedIn,
+    #[snafu(display("yandex music answers with {} to {}, try again later", status, url))]
+    Status { url: String, status: StatusCode },
+    #[snafu(display(
+        "unexpected layout of {} page, the player needs an update: {}",
+        page,
+        source
+    ))]
+    MarkupChanged {
+        page: &'static str,
+        source: unhtml::Error,
+    },
+    #[snafu(display("unexpected json answer, the player needs an update: {}", source))]
+    JsonError {
+        body: String,
+        source: serde_json::Error,
+    },
+    #[snafu(display("unexpected download info, the player needs an update: {}", source))]
     XmlError {
         body: String,
         source: serde_xml_rs::Error,
     },
-    #[snafu(display("timeouted"))]
-    Timeout { source: tokio::time::Elapsed },
+}
+
+impl Error {
+    /// Whether the request to another backend might succeed, e.g. a removed handler or
+    /// a changed format of its answer
+    fn is_backend_specific(&self) -> bool {
+        match self {
+            // the pages are requested by the urls themselves
+            Error::NotFound { .. } | Error::JsonError { .. } => true,
+            Error::Status { status, .. } => status.is_client_error(),
+            _ => false,
+        }
+    }
+
+    /// Whether the same request might succeed later
+    fn is_transient(&self) -> bool {
+        match self {
+            Error::HttpError { .. } | Error::Timeout { .. } | Error::RateLimited { .. } => true,
+            Error::Status { status, .. } => status.is_server_error(),
+            _ => false,
+        }
+    }
 }
 
 pub type Result<T> = StdResult<T, Error>;
 
+fn parse_html<R: FromHtml, T: From<R>>(body: &str, page: &'static str) -> Result<T> {
+    R::from_html(body)
+        .map(Into::into)
+        .context(MarkupChanged { page })
+}
+
+/// Central block of every music page, the anti-bot page has none
+#[derive(FromHtml)]
+struct PageFrameRaw {
+    #[html(selector = "div.centerblock", attr = "class")]
+    _class: String,
+}
+
+/// Parses the page, the markup is checked for the captcha only if it is not the expected one
+fn parse_page<R: FromHtml, T: From<R>>(url: &str, body: &str, page: &'static str) -> Result<T> {
+    let parsed = parse_html::<R, T>(body, page);
+    if parsed.is_err() || PageFrameRaw::from_html(body).is_err() {
+        if let Some(url) = captcha_markup(url, body) {
+            return Err(Error::Captcha { url });
+        }
+    }
+    parsed
+}
+
+/// Phrases of the anti-bot page, shown instead of the requested one
+const ANTI_BOT_MARKERS: &[&str] = &[
+    "unusual traffic",
+    "похожи на автоматические",
+    "captcha__image",
+];
+
+/// Captcha url, if the request is redirected to the captcha or the api answers with it
+fn detect_captcha(location: &str, body: &str) -> Option<String> {
+    let redirected = Url::parse(location)
+        .map(|url| url.path().starts_with("/showcaptcha"))
+        .unwrap_or(false);
+    if redirected {
+        return Some(location.to_string());
+    }
+
+    // json api answers with `{"type":"captcha","captcha":{"captcha-page":"..."}}`
+    if !body.trim_start().starts_with('{') || !body.contains("\"captcha\"") {
+        return None;
+    }
+    let json: serde_json::Value = serde_json::from_str(body).ok()?;
+    if json["type"] != "captcha" {
+        return None;
+    }
+    let page = json["captcha"]["captcha-page"].as_str().unwrap_or(location);
+    Some(page.to_string())
+}
+
+/// Captcha url, if the html page is an anti-bot one rather than the requested one
+fn captcha_markup(location: &str, body: &str) -> Option<String> {
+    // html pages refer to the captcha form
+    if let Some(pos) = body.find("showcaptcha") {
+        let start = body[..pos].rfind('"').map(|pos| pos + 1).unwrap_or(pos);
+        let end = body[pos..]
+            .find('"')
+            .map(|end| pos + end)
+            .unwrap_or(body.len());
+        let url = body[start..end].replace("&amp;", "&");
+        return Some(if url.starts_with('/') {
+            format!("{}{}", BASE_URL, url)
+        } else {
+            url
+        });
+    }
+
+    if ANTI_BOT_MARKERS.iter().any(|marker| body.contains(marker)) {
+        Some(location.to_string())
+    } else {
+        None
+    }
+}
+
+/// Whether the url is of the site or the api, only they get the credentials
+fn is_music_host(url: &str) -> bool {
+    let url = match Url::parse(url) {
+        Ok(url) => url,
+        Err(_) => return false,
+    };
+    match url.host_str() {
+        Some("api.music.yandex.net") => true,
+        Some(host) => is_site_host(host),
+        None => false,
+    }
+}
+
+/// Whether the host is of the site at one of its zones
+fn is_site_host(host: &str) -> bool {
+    match host.strip_prefix("music.yandex.") {
+        Some(zone) => matches!(zone, "ru" | "by" | "kz" | "uz" | "com" | "com.tr"),
+        None => false,
+    }
+}
+
 /// Yandex Music info/media provider
+#[derive(Clone)]
 pub struct Provider {
     client: Client,
+    /// Cookie of a session, which passed the captcha
+    cookie: Arc<RwLock<Option<String>>>,
+    /// Account of the logged in user, shared between the clones
+    credentials: Arc<RwLock<Option<Credentials>>>,
+    /// Uid of the account, it is requested once after the login
+    account: Arc<RwLock<Option<meta::Account>>>,
+    timeout: Duration,
+    retries: u32,
+    backoff: Duration,
+    /// Pages shared between the clones, stream urls are never stored there since they are signed
+    cache: Arc<HttpCache>,
+    /// Whether the cached pages are used, they are stored anyway
+    read_cache: bool,
+    backend: Backend,
+}
+
+/// Listening feedback, which the station picks the following tracks by
+#[derive(Debug, Clone, Copy)]
+pub enum Feedback {
+    RadioStarted,
+    TrackFinished {
+        track_id: meta::TrackId,
+        played: Duration,
+    },
+    Skip {
+        track_id: meta::TrackId,
+        played: Duration,
+    },
+    Like {
+        track_id: meta::TrackId,
+    },
+}
+
+/// Catalogue item, which a yandex music link points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Link {
+    Artist(meta::ArtistId),
+    Album(meta::AlbumId),
+    /// Newer links omit the album of the track
+    Track {
+        album: Option<meta::AlbumId>,
+        track: meta::TrackId,
+    },
+    Playlist {
+        owner: String,
+        kind: u32,
+    },
+}
+
+impl Link {
+    /// Parses a link like `https://music.yandex.ru/album/4766/track/57703`, anything else,
+    /// including the paths without the host, is rejected to not mistake a query for a link
+    pub fn parse(link: &str) -> Option<Self> {
+        let url = reqwest::Url::parse(link.trim()).ok()?;
+        if !is_site_host(url.host_str()?) {
+            return None;
+        }
+        let path = url.path();
+        if let Ok(track) = meta::TrackId::from_url(path) {
+            let album = meta::AlbumId::from_url(path).ok();
+            return Some(Link::Track { album, track });
+        }
+        if let Ok(album) = meta::AlbumId::from_url(path) {
+            return Some(Link::Album(album));
+        }
+        if let Ok(artist) = meta::ArtistId::from_url(path) {
+            return Some(Link::Artist(artist));
+        }
+        match path.split('/').collect::<Vec<_>>().as_slice() {
+            ["", "users", owner, "playlists", kind, ..] => Some(Link::Playlist {
+                owner: owner.to_string(),
+                kind: kind.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Source of the metadata, the HTML pages are scraped if the JSON handlers fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum Backend {
+    Html,
+    Json,
 }
 
 #[derive(Display, Clone, Copy)]
@@ -227,157 +571,544 @@ enum SearchType {
     Albums,
     Tracks,
     Artists,
+    Playlists,
 }
 
 impl SearchType {
-    fn search_url(self, search_text: &str) -> String {
-        format!(
-            "{}/search?text={}&type={}",
-            BASE_URL,
-            search_text, // TODO: url encode
-            self.to_string()
-        )
+    /// Url of the search results page, pages are counted from zero
+    fn search_url(self, search_text: &str, page: usize) -> String {
+        let params = [
+            ("text", search_text.to_string()),
+            ("type", self.to_string()),
+            ("page", page.to_string()),
+        ];
+        Url::parse_with_params(&format!("{}/search", BASE_URL), &params)
+            .expect("search url is always valid")
+            .to_string()
     }
 }
 
 impl Provider {
-    pub fn new() -> Self {
+    pub fn new(config: &ProviderConfig, cache: &CacheConfig) -> Self {
         Self {
             client: Client::new(),
+            cookie: Arc::new(RwLock::new(None)),
+            credentials: Arc::new(RwLock::new(None)),
+            account: Arc::new(RwLock::new(None)),
+            timeout: config.timeout,
+            retries: config.retries,
+            backoff: config.backoff,
+            cache: Arc::new(HttpCache::new(cache)),
+            read_cache: true,
+            backend: config.backend,
         }
     }
 
-    pub async fn artists_search(&self, text: &str) -> Result<meta::Artists> {
-        let url = SearchType::Artists.search_url(text);
+    /// Provider, which bypasses the cache and refreshes it with the fetched pages
+    pub fn fresh(&self) -> Self {
+        Self {
+            read_cache: false,
+            ..self.clone()
+        }
+    }
 
-        tokio::time::timeout(
-            REQUEST_TIMEOUT,
-            self.client.get(&url).send().and_then(|r| r.text()),
-        )
-        .await
-        .context(Timeout {})?
-        .context(HttpError { url })
-        .and_then(|body| {
-            ArtistsRaw::from_html(&body)
-                .map(Into::into)
-                .context(HtmlError {})
-        })
+    /// Attaches the cookie to all the following requests, e.g. after the captcha is solved
+    pub fn set_cookie(&self, cookie: String) {
+        *self.cookie.write().unwrap() = Some(cookie);
     }
 
-    pub async fn artist_albums(&self, artist: &meta::Artist) -> Result<meta::Albums> {
-        let url = format!("{}{}/albums", BASE_URL, artist.url);
+    /// Attaches the credentials to all the following requests
+    pub fn login(&self, credentials: Credentials) {
+        *self.credentials.write().unwrap() = Some(credentials);
+        *self.account.write().unwrap() = None;
+    }
 
-        tokio::time::timeout(
-            REQUEST_TIMEOUT,
-            self.client.get(&url).send().and_then(|r| r.text()),
-        )
-        .await
-        .context(Timeout {})?
-        .context(HttpError { url })
-        .and_then(|body| {
-            AlbumsRaw::from_html(&body)
-                .map(Into::into)
-                .context(HtmlError {})
+    pub fn logout(&self) {
+        *self.credentials.write().unwrap() = None;
+        *self.account.write().unwrap() = None;
+    }
+
+    pub fn is_logged_in(&self) -> bool {
+        self.credentials.read().unwrap().is_some()
+    }
+
+    pub fn has_library(&self) -> bool {
+        matches!(*self.credentials.read().unwrap(), Some(ref credentials) if credentials.has_library())
+    }
+
+    /// Fetches the page, transient failures are retried with an exponential backoff
+    async fn get(&self, url: &str, headers: &[(&'static str, String)]) -> Result<String> {
+        self.retrying(url, || {
+            let mut request = self.client.get(url);
+            for (name, value) in headers {
+                request = request.header(*name, value.as_str());
+            }
+            request
         })
+        .await
+    }
+
+    /// Posts the form to the api, the requests are idempotent, so they are retried as well
+    async fn post(&self, url: &str, form: &[(&'static str, String)]) -> Result<String> {
+        self.retrying(url, || self.client.post(url).form(form))
+            .await
+    }
+
+    async fn post_json(&self, url: &str, body: &serde_json::Value) -> Result<String> {
+        self.retrying(url, || self.client.post(url).json(body))
+            .await
+    }
+
+    async fn retrying(&self, url: &str, request: impl Fn() -> RequestBuilder) -> Result<String> {
+        let mut delay = self.backoff;
+        let mut attempt = 0;
+        loop {
+            match self.send(url, request()).await {
+                Err(err) if err.is_transient() && attempt < self.retries => {
+                    log::warn!("{}, retrying in {:?}", err, delay);
+                    tokio::time::delay_for(delay).await;
+                    attempt += 1;
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Fetches and parses the page, it is cached only if parsed successfully
+    async fn get_parsed<T>(
+        &self,
+        url: &str,
+        headers: &[(&'static str, String)],
+        kind: CacheKind,
+        parse: impl Fn(&str) -> Result<T>,
+    ) -> Result<T> {
+        let key = self.cache_key(url);
+        if self.read_cache {
+            if let Some(body) = self.cache.get(&key, kind) {
+                match parse(&body) {
+                    Ok(parsed) => return Ok(parsed),
+                    Err(err) => log::warn!("ignoring cached {}: {}", url, err),
+                }
+            }
+        }
+
+        let body = self.get(url, headers).await?;
+        let parsed = parse(&body)?;
+        self.cache.put(&key, &body);
+        Ok(parsed)
+    }
+
+    /// Pages are cached per account, so that a library page is never shown to another one
+    fn cache_key(&self, url: &str) -> String {
+        match *self.credentials.read().unwrap() {
+            Some(ref credentials) => {
+                // the credentials themselves are not written to the disk
+                let mut hasher = DefaultHasher::new();
+                credentials.hash(&mut hasher);
+                format!("{} account:{:016x}", url, hasher.finish())
+            }
+            None => url.to_string(),
+        }
+    }
+
+    async fn get_page<R: FromHtml, T: From<R>>(
+        &self,
+        url: &str,
+        kind: CacheKind,
+        page: &'static str,
+    ) -> Result<T> {
+        self.get_parsed(url, &[], kind, |body| parse_page::<R, T>(url, body, page))
+            .await
+    }
+
+    async fn get_json<T>(
+        &self,
+        url: &str,
+        kind: CacheKind,
+        parse: impl Fn(&str) -> Result<T>,
+    ) -> Result<T> {
+        let headers = [("X-Requested-With", "XMLHttpRequest".to_string())];
+        self.get_parsed(url, &headers, kind, parse).await
+    }
+
+    /// Uses the JSON handlers if they are chosen, the HTML pages are scraped if the handlers
+    /// fail in a way, which the pages might not
+    async fn with_fallback<T>(
+        &self,
+        json: impl Future<Output = Result<T>>,
+        html: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        if self.backend == Backend::Json {
+            match json.await {
+                Err(err) if err.is_backend_specific() => {
+                    log::warn!("json backend failed, scraping the page instead: {}", err)
+                }
+                result => return result,
+            }
+        }
+        html.await
+    }
+
+    /// Attaches the account credentials and the session cookie to the request, unless it is
+    /// sent to another host than the music ones, e.g. the storage of the tracks
+    fn authorize(&self, url: &str, mut request: RequestBuilder) -> RequestBuilder {
+        if !is_music_host(url) {
+            return request;
+        }
+        let credentials = self.credentials.read().unwrap().clone();
+        let cookies = match credentials {
+            Some(Credentials::Token(token)) => {
+                request = request.header("Authorization", format!("OAuth {}", token));
+                None
+            }
+            Some(Credentials::Cookie(cookie)) => Some(cookie),
+            None => None,
+        };
+        let cookies = cookies
+            .into_iter()
+            .chain(self.cookie.read().unwrap().clone())
+            .collect::<Vec<_>>();
+        if !cookies.is_empty() {
+            request = request.header("Cookie", cookies.join("; "));
+        }
+        request
+    }
+
+    async fn send(&self, url: &str, request: RequestBuilder) -> Result<String> {
+        let request = self.authorize(url, request);
+        let response = async {
+            let response = request.send().await?;
+            let status = response.status();
+            let location = response.url().to_string();
+            Ok((status, location, response.text().await?))
+        };
+        let (status, location, body) = tokio::time::timeout(self.timeout, response)
+            .await
+            .context(Timeout { url })?
+            .context(HttpError { url })?;
+
+        if let Some(url) = detect_captcha(&location, &body) {
+            return Err(Error::Captcha { url });
+        }
+        match status {
+            StatusCode::NOT_FOUND => Err(Error::NotFound { url: url.into() }),
+            StatusCode::TOO_MANY_REQUESTS => Err(Error::RateLimited { url: url.into() }),
+            status if !status.is_success() => Err(Error::Status {
+                url: url.into(),
+                status,
+            }),
+            _ => Ok(body),
+        }
+    }
+
+    pub async fn artists_search(&self, text: &str, page: usize) -> Result<meta::Artists> {
+        let json = async {
+            let url = json::search_url("artists", text, page);
+            self.get_json(&url, CacheKind::Search, json::parse_artists)
+                .await
+        };
+        let html = async {
+            let url = SearchType::Artists.search_url(text, page);
+            self.get_page::<ArtistsRaw, _>(&url, CacheKind::Search, "artist search")
+                .await
+        };
+        self.with_fallback(json, html).await
+    }
+
+    pub async fn artist_albums(&self, artist: &meta::Artist) -> Result<meta::Albums> {
+        let json = async {
+            let url = json::artist_url(artist.id, "albums");
+            self.get_json(&url, CacheKind::ArtistAlbums, json::parse_artist_albums)
+                .await
+        };
+        let html = async {
+            let url = format!("{}{}/albums", BASE_URL, artist.id.path());
+            self.get_page::<AlbumsRaw, _>(&url, CacheKind::ArtistAlbums, "artist albums")
+                .await
+        };
+        self.with_fallback(json, html).await
     }
 
     pub async fn artist_tracks(&self, artist: &meta::Artist) -> Result<meta::Tracks> {
-        let url = format!("{}{}/tracks", BASE_URL, artist.url);
+        let json = async {
+            let url = json::artist_url(artist.id, "tracks");
+            self.get_json(&url, CacheKind::ArtistTracks, json::parse_artist_tracks)
+                .await
+        };
+        let html = async {
+            let url = format!("{}{}/tracks", BASE_URL, artist.id.path());
+            self.get_page::<TracksRaw, _>(&url, CacheKind::ArtistTracks, "artist tracks")
+                .await
+        };
+        self.with_fallback(json, html).await
+    }
 
-        tokio::time::timeout(
-            REQUEST_TIMEOUT,
-            self.client.get(&url).send().and_then(|r| r.text()),
-        )
-        .await
-        .context(Timeout {})?
-        .context(HttpError { url })
-        .and_then(|body| {
-            TracksRaw::from_html(&body)
-                .map(Into::into)
-                .context(HtmlError {})
-        })
+    /// Top tracks of the week
+    pub async fn chart(&self) -> Result<meta::Tracks> {
+        let url = format!("{}/chart", BASE_URL);
+        self.get_page::<TracksRaw, _>(&url, CacheKind::Landing, "chart")
+            .await
     }
 
-    pub async fn album_search(&self, text: &str) -> Result<meta::Albums> {
-        let url = SearchType::Albums.search_url(text);
+    pub async fn new_releases(&self) -> Result<meta::Albums> {
+        let url = format!("{}/new-releases", BASE_URL);
+        self.get_page::<AlbumsRaw, _>(&url, CacheKind::Landing, "new releases")
+            .await
+    }
 
-        tokio::time::timeout(
-            REQUEST_TIMEOUT,
-            self.client.get(&url).send().and_then(|r| r.text()),
-        )
-        .await
-        .context(Timeout {})?
-        .context(HttpError { url })
-        .and_then(|body| {
-            AlbumsRaw::from_html(&body)
-                .map(Into::into)
-                .context(HtmlError {})
-        })
+    pub async fn new_playlists(&self) -> Result<meta::Playlists> {
+        let url = format!("{}/new-playlists", BASE_URL);
+        self.get_page::<PlaylistsRaw, _>(&url, CacheKind::Landing, "new playlists")
+            .await
     }
 
-    pub async fn track_search(&self, text: &str) -> Result<meta::Tracks> {
-        let url = SearchType::Tracks.search_url(text);
+    pub async fn genres(&self) -> Result<meta::Genres> {
+        let url = format!("{}/genres", BASE_URL);
+        self.get_page::<GenresRaw, _>(&url, CacheKind::Landing, "genres")
+            .await
+    }
 
-        tokio::time::timeout(
-            REQUEST_TIMEOUT,
-            self.client.get(&url).send().and_then(|r| r.text()),
-        )
-        .await
-        .context(Timeout {})?
-        .context(HttpError { url })
-        .and_then(|body| {
-            TracksRaw::from_html(&body)
-                .map(Into::into)
-                .context(HtmlError {})
+    pub async fn genre_tracks(&self, genre: &meta::Genre) -> Result<meta::Tracks> {
+        let url = format!("{}/genre/{}/tracks", BASE_URL, genre.id);
+        self.get_page::<TracksRaw, _>(&url, CacheKind::Landing, "genre tracks")
+            .await
+    }
+
+    pub async fn genre_albums(&self, genre: &meta::Genre) -> Result<meta::Albums> {
+        let url = format!("{}/genre/{}/albums", BASE_URL, genre.id);
+        self.get_page::<AlbumsRaw, _>(&url, CacheKind::Landing, "genre albums")
+            .await
+    }
+
+    /// Overview of the artist, its discography is split by the album types
+    pub async fn artist_page(&self, artist: &meta::Artist) -> Result<meta::ArtistPage> {
+        let url = json::artist_brief_url(artist.id);
+        self.get_parsed(&url, &[], CacheKind::ArtistAlbums, |body| {
+            json::parse_artist_page(artist, body)
         })
+        .await
+    }
+
+    /// Playlists featured at the artist page
+    pub async fn artist_playlists(&self, artist: &meta::Artist) -> Result<meta::Playlists> {
+        // they change as rarely as the albums of the artist
+        let json = async {
+            let url = json::artist_url(artist.id, "playlists");
+            self.get_json(&url, CacheKind::ArtistAlbums, json::parse_artist_playlists)
+                .await
+        };
+        let html = async {
+            let url = format!("{}{}", BASE_URL, artist.id.path());
+            self.get_page::<PlaylistsRaw, _>(&url, CacheKind::ArtistAlbums, "artist")
+                .await
+        };
+        self.with_fallback(json, html).await
+    }
+
+    pub async fn playlist_search(&self, text: &str, page: usize) -> Result<meta::Playlists> {
+        let json = async {
+            let url = json::search_url("playlists", text, page);
+            self.get_json(&url, CacheKind::Search, json::parse_playlist_search)
+                .await
+        };
+        let html = async {
+            let url = SearchType::Playlists.search_url(text, page);
+            self.get_page::<PlaylistsRaw, _>(&url, CacheKind::Search, "playlist search")
+                .await
+        };
+        self.with_fallback(json, html).await
+    }
+
+    pub async fn album_search(&self, text: &str, page: usize) -> Result<meta::Albums> {
+        let json = async {
+            let url = json::search_url("albums", text, page);
+            self.get_json(&url, CacheKind::Search, json::parse_albums)
+                .await
+        };
+        let html = async {
+            let url = SearchType::Albums.search_url(text, page);
+            self.get_page::<AlbumsRaw, _>(&url, CacheKind::Search, "album search")
+                .await
+        };
+        self.with_fallback(json, html).await
+    }
+
+    pub async fn track_search(&self, text: &str, page: usize) -> Result<meta::Tracks> {
+        let json = async {
+            let url = json::search_url("tracks", text, page);
+            self.get_json(&url, CacheKind::Search, json::parse_tracks)
+                .await
+        };
+        let html = async {
+            let url = SearchType::Tracks.search_url(text, page);
+            self.get_page::<TracksRaw, _>(&url, CacheKind::Search, "track search")
+                .await
+        };
+        self.with_fallback(json, html).await
     }
 
     pub async fn album_tracks(&self, album: &meta::Album) -> Result<meta::Tracks> {
-        let url = format!("{}{}", BASE_URL, album.url);
+        let json = async {
+            let url = json::album_url(album.id);
+            self.get_json(&url, CacheKind::AlbumTracks, json::parse_album_tracks)
+                .await
+        };
+        let html = async {
+            let url = format!("{}{}", BASE_URL, album.id.path());
+            self.get_page::<TracksRaw, _>(&url, CacheKind::AlbumTracks, "album")
+                .await
+        };
+        self.with_fallback(json, html).await
+    }
 
-        tokio::time::timeout(
-            REQUEST_TIMEOUT,
-            self.client.get(&url).send().and_then(|r| r.text()),
-        )
-        .await
-        .context(Timeout {})?
-        .context(HttpError { url })
-        .and_then(|body| {
-            TracksRaw::from_html(&body)
-                .map(Into::into)
-                .context(HtmlError {})
-        })
+    /// Account of the user logged in with a token, the api does not accept the cookies
+    async fn account(&self) -> Result<meta::Account> {
+        if !self.has_library() {
+            return Err(Error::NotLoggedIn);
+        }
+        if let Some(account) = self.account.read().unwrap().clone() {
+            return Ok(account);
+        }
+
+        let account = json::parse_account(&self.get(&json::account_url(), &[]).await?)?;
+        log::info!("library of {} is requested", account.login);
+        *self.account.write().unwrap() = Some(account.clone());
+        Ok(account)
+    }
+
+    /// Library pages are personal and change often, so they are never cached
+    pub async fn liked_tracks(&self) -> Result<meta::Tracks> {
+        let account = self.account().await?;
+        let url = json::library_url(&account, "likes/tracks");
+        let ids = json::parse_liked_track_ids(&self.get(&url, &[]).await?)?;
+        if ids.is_empty() {
+            return Ok(meta::Tracks { tracks: vec![] });
+        }
+
+        self.tracks_by_ids(&ids).await
+    }
+
+    pub async fn tracks_by_ids(&self, ids: &[meta::TrackId]) -> Result<meta::Tracks> {
+        let ids = itertools::join(ids, ",");
+        let body = self
+            .post(&json::tracks_url(), &[("track-ids", ids)])
+            .await?;
+        json::parse_tracks_by_ids(&body)
+    }
+
+    pub async fn liked_albums(&self) -> Result<meta::Albums> {
+        let account = self.account().await?;
+        let url = json::library_url(&account, "likes/albums?rich=true");
+        json::parse_liked_albums(&self.get(&url, &[]).await?)
+    }
+
+    pub async fn liked_artists(&self) -> Result<meta::Artists> {
+        let account = self.account().await?;
+        let url = json::library_url(&account, "likes/artists?with-timestamps=false");
+        json::parse_liked_artists(&self.get(&url, &[]).await?)
+    }
+
+    pub async fn user_playlists(&self) -> Result<meta::Playlists> {
+        let account = self.account().await?;
+        let url = json::library_url(&account, "playlists/list");
+        json::parse_playlists(&self.get(&url, &[]).await?)
+    }
+
+    /// Tracks of the playlist, they are never cached since the playlists are edited
+    pub async fn playlist_tracks(&self, playlist: &meta::Playlist) -> Result<meta::Tracks> {
+        let json = async {
+            let url = json::playlist_url(playlist);
+            json::parse_playlist_tracks(&self.get(&url, &[]).await?)
+        };
+        let html = async {
+            let url = format!("{}{}", BASE_URL, playlist.url);
+            parse_page::<TracksRaw, _>(&url, &self.get(&url, &[]).await?, "playlist")
+        };
+        self.with_fallback(json, html).await
+    }
+
+    /// Adds the item to the library of the user or removes it from there
+    pub async fn set_liked(&self, item: &meta::Likeable, liked: bool) -> Result<()> {
+        let account = self.account().await?;
+        let (section, field, id) = json::like_params(item);
+        let action = if liked { "add-multiple" } else { "remove" };
+        let url = json::library_url(&account, &format!("likes/{}/{}", section, action));
+        self.post(&url, &[(field, id)]).await.map(drop)
+    }
+
+    /// Next tracks of the station, which follow the last played one
+    pub async fn station_tracks(
+        &self,
+        station: &meta::Station,
+        last: Option<meta::TrackId>,
+    ) -> Result<meta::StationTracks> {
+        let url = json::station_tracks_url(station, last);
+        json::parse_station_tracks(&self.get(&url, &[]).await?)
+    }
+
+    pub async fn station_feedback(
+        &self,
+        station: &meta::Station,
+        batch_id: &str,
+        feedback: Feedback,
+    ) -> Result<()> {
+        let url = json::station_feedback_url(station, batch_id);
+        self.post_json(&url, &json::feedback_body(feedback))
+            .await
+            .map(drop)
+    }
+
+    /// Downloads the whole track file, which usually takes longer than the request timeout
+    pub async fn download_track(&self, track: &meta::Track) -> Result<Vec<u8>> {
+        let url = self.get_track_url(track).await?;
+        self.get_bytes(url).await
+    }
+
+    /// Cover image of the album, if it has one
+    pub async fn album_cover(&self, album: &meta::Album) -> Result<Option<Vec<u8>>> {
+        if let Some(ref url) = album.cover_url {
+            return self.get_bytes(url.clone()).await.map(Some);
+        }
+        let url = format!("{}{}", BASE_URL, album.id.path());
+        let cover = self
+            .get_page::<CoverRaw, CoverRaw>(&url, CacheKind::AlbumTracks, "album")
+            .await?;
+        let url = match cover.url {
+            // `//avatars.yandex.net/get-music-content/...`
+            Some(url) if url.starts_with("//") => format!("https:{}", url),
+            Some(url) => url,
+            None => return Ok(None),
+        };
+        self.get_bytes(url).await.map(Some)
+    }
+
+    async fn get_bytes(&self, url: String) -> Result<Vec<u8>> {
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .context(HttpError { url: url.as_str() })?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::Status { url, status });
+        }
+        let data = response.bytes().await.context(HttpError { url })?;
+        Ok(data.to_vec())
     }
 
     pub async fn get_track_url(&self, track: &meta::Track) -> Result<String> {
         let url = format!("https://music.yandex.ru/api/v2.1/handlers/track/{}:{}/web-album-track-track-saved/download/m", track.track_id, track.album_id);
+        let retpath = format!("https%3A%2F%2Fmusic.yandex.ru%2Falbum%2F{}", track.album_id);
 
-        let url = tokio::time::timeout(
-            REQUEST_TIMEOUT,
-            self.client
-                .get(&url)
-                .header(
-                    "X-Retpath-Y",
-                    format!("https%3A%2F%2Fmusic.yandex.ru%2Falbum%2F{}", track.album_id),
-                )
-                .send()
-                .and_then(|r| r.json::<BalancerResponse>()),
-        )
-        .await
-        .context(Timeout {})?
-        .context(HttpError { url })?
-        .src;
-
-        let info = tokio::time::timeout(
-            REQUEST_TIMEOUT,
-            self.client.get(&url).send().and_then(|r| r.text()),
-        )
-        .await
-        .context(Timeout {})?
-        .context(HttpError { url })
-        .and_then(|response| {
-            serde_xml_rs::from_str::<DownloadInfo>(&response).context(XmlError { body: response })
-        })?;
+        let body = self.get(&url, &[("X-Retpath-Y", retpath)]).await?;
+        let url = serde_json::from_str::<BalancerResponse>(&body)
+            .context(JsonError { body })?
+            .src;
+
+        let body = self.get(&url, &[]).await?;
+        let info = serde_xml_rs::from_str::<DownloadInfo>(&body).context(XmlError { body })?;
 
         Ok(format!(
             "https://{}/get-mp3/11111111111111111111111111111111/{}{}?track-id={}&play=false",
@@ -395,5 +1126,163 @@ mod tests {
         assert_eq!(SearchType::Albums.to_string(), "albums");
         assert_eq!(SearchType::Tracks.to_string(), "tracks");
         assert_eq!(SearchType::Artists.to_string(), "artists");
+        assert_eq!(SearchType::Playlists.to_string(), "playlists");
+    }
+
+    #[test]
+    fn test_search_url() {
+        assert_eq!(
+            SearchType::Albums.search_url("pink floyd & co", 2),
+            "https://music.yandex.ru/search?text=pink+floyd+%26+co&type=albums&page=2"
+        );
+    }
+
+    #[test]
+    fn test_parse_links() {
+        assert_eq!(
+            Link::parse("https://music.yandex.ru/album/4766/track/57703"),
+            Some(Link::Track {
+                album: Some(meta::AlbumId::yandex(4766)),
+                track: meta::TrackId::yandex(57703),
+            })
+        );
+        assert_eq!(
+            Link::parse("https://music.yandex.com/track/57703?utm_source=web"),
+            Some(Link::Track {
+                album: None,
+                track: meta::TrackId::yandex(57703),
+            })
+        );
+        assert_eq!(
+            Link::parse(" https://music.yandex.ru/artist/79215/albums "),
+            Some(Link::Artist(meta::ArtistId::yandex(79215)))
+        );
+        assert_eq!(
+            Link::parse("https://music.yandex.ru/users/music-blog/playlists/1000"),
+            Some(Link::Playlist {
+                owner: "music-blog".to_string(),
+                kind: 1000,
+            })
+        );
+        assert_eq!(Link::parse("/album/4766"), None);
+        assert_eq!(Link::parse("https://example.com/album/4766"), None);
+        assert_eq!(
+            Link::parse("https://music.yandex.ru.example.com/album/1"),
+            None
+        );
+        assert_eq!(Link::parse("the dark side of the moon"), None);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("6:53"), Some(Duration::from_secs(413)));
+        assert_eq!(parse_duration("1:02:03"), Some(Duration::from_secs(3723)));
+        assert_eq!(parse_duration("live"), None);
+    }
+
+    #[test]
+    fn test_parse_genres() {
+        let page = r#"<div class="centerblock">
+            <a href="/genre/rock"><img src="cover.jpg"></a>
+            <a href="/genre/rock">Rock</a>
+            <a href="/genre/jazz">Jazz</a>
+            <a href="/artist/1">Not a genre</a>
+        </div>"#;
+        let genres: meta::Genres = parse_html::<GenresRaw, _>(page, "genres").unwrap();
+        let ids = genres
+            .genres
+            .iter()
+            .map(|genre| (genre.id.as_str(), genre.name.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(ids, [("rock", "Rock"), ("jazz", "Jazz")]);
+    }
+
+    #[test]
+    fn test_music_hosts() {
+        assert!(is_music_host("https://music.yandex.ru/handlers/album.jsx"));
+        assert!(is_music_host("https://music.yandex.by/search"));
+        assert!(is_music_host(
+            "https://api.music.yandex.net/users/1/likes/tracks"
+        ));
+        assert!(!is_music_host(
+            "https://storage.mds.yandex.net/download-info/1/2"
+        ));
+        assert!(!is_music_host("https://music.yandex.ru.example.com/"));
+        assert!(!is_music_host("https://example.com/music.yandex.ru"));
+    }
+
+    #[test]
+    fn test_captcha_detection() {
+        let page = "https://music.yandex.ru/search";
+        assert_eq!(
+            detect_captcha("https://music.yandex.ru/showcaptcha?retpath=x", "").as_deref(),
+            Some("https://music.yandex.ru/showcaptcha?retpath=x")
+        );
+        assert_eq!(
+            captcha_markup(
+                page,
+                r#"<form action="/showcaptcha?retpath=x&amp;key=y" method="get">"#
+            )
+            .as_deref(),
+            Some("https://music.yandex.ru/showcaptcha?retpath=x&key=y")
+        );
+        assert_eq!(
+            detect_captcha(
+                page,
+                r#"{"type":"captcha","captcha":{"captcha-page":"https:\/\/music.yandex.ru\/showcaptcha?x"}}"#
+            )
+            .as_deref(),
+            Some("https://music.yandex.ru/showcaptcha?x")
+        );
+        assert_eq!(
+            captcha_markup(page, "we have detected unusual traffic").as_deref(),
+            Some(page)
+        );
+        assert_eq!(captcha_markup(page, "<div class=\"centerblock\">"), None);
+    }
+
+    #[test]
+    fn test_captcha_mentioned() {
+        let page = "https://music.yandex.ru/genres";
+        let body = r#"<div class="centerblock">
+            <a href="/genre/rock">Rock</a>
+            <a href="/album/1">Unusual Traffic</a>
+            <a href="/album/2">showcaptcha</a>
+        </div>"#;
+        assert_eq!(detect_captcha(page, body), None);
+        let genres: meta::Genres = parse_page::<GenresRaw, _>(page, body, "genres").unwrap();
+        assert_eq!(genres.genres.len(), 1);
+
+        let json = r#"{"type":"album","title":"captcha","tracks":["captcha"]}"#;
+        assert_eq!(
+            detect_captcha("https://music.yandex.ru/handlers/album.jsx", json),
+            None
+        );
+        let redirect = "https://music.yandex.ru/search?text=showcaptcha";
+        assert_eq!(detect_captcha(redirect, ""), None);
+
+        let captcha = r#"<p>we have detected unusual traffic</p>"#;
+        assert!(matches!(
+            parse_page::<GenresRaw, meta::Genres>(page, captcha, "genres"),
+            Err(Error::Captcha { .. })
+        ));
+    }
+
+    #[test]
+    fn test_transient_errors() {
+        let url = String::new;
+        assert!(Error::RateLimited { url: url() }.is_transient());
+        assert!(Error::Status {
+            url: url(),
+            status: StatusCode::BAD_GATEWAY
+        }
+        .is_transient());
+        assert!(!Error::Status {
+            url: url(),
+            status: StatusCode::FORBIDDEN
+        }
+        .is_transient());
+        assert!(!Error::NotFound { url: url() }.is_transient());
+        assert!(!Error::Captcha { url: url() }.is_transient());
     }
 }
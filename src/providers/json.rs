@@ -0,0 +1,767 @@
+use std::convert::{TryFrom, TryInto};
+use std::result::Result as StdResult;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use snafu::ResultExt;
+
+use super::{Feedback, JsonError, Result, BASE_URL};
+use crate::meta;
+
+// This file contains the raw types of the JSON handlers used by the `json` backend and of the
+// api, which the library of the logged in user is requested from
+
+/// Api of the mobile clients, it accepts the OAuth tokens
+const API_URL: &str = "https://api.music.yandex.net";
+
+/// Ids are numbers at some handlers and strings at others
+fn id<'de, D: Deserializer<'de>>(deserializer: D) -> StdResult<u32, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Id {
+        Number(u32),
+        String(String),
+    }
+
+    match Id::deserialize(deserializer)? {
+        Id::Number(id) => Ok(id),
+        Id::String(id) => id.parse().map_err(de::Error::custom),
+    }
+}
+
+// {"id":79215,"name":"Pink Floyd","various":false,"composer":false,"genres":[]}
+#[derive(Deserialize)]
+struct ArtistJson {
+    #[serde(deserialize_with = "id")]
+    id: u32,
+    name: String,
+}
+
+impl From<ArtistJson> for meta::Artist {
+    fn from(json: ArtistJson) -> Self {
+        Self {
+            id: meta::ArtistId::yandex(json.id),
+            name: json.name,
+        }
+    }
+}
+
+// {"id":297567,"title":"The Wall","year":1979,"version":"Remastered","artists":[...]}
+#[derive(Deserialize)]
+struct AlbumJson {
+    #[serde(deserialize_with = "id")]
+    id: u32,
+    title: String,
+    #[serde(default)]
+    year: u16,
+    version: Option<String>,
+    #[serde(default)]
+    artists: Vec<ArtistJson>,
+    /// `single` or `compilation`, regular albums have none
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    #[serde(rename = "trackCount")]
+    track_count: Option<u32>,
+    genre: Option<String>,
+    #[serde(default)]
+    labels: Vec<LabelJson>,
+    #[serde(rename = "coverUri")]
+    cover_uri: Option<String>,
+}
+
+/// Labels are either names or `{"id":1,"name":"EMI"}`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LabelJson {
+    Name(String),
+    Label { name: String },
+}
+
+impl LabelJson {
+    fn name(self) -> String {
+        match self {
+            LabelJson::Name(name) | LabelJson::Label { name } => name,
+        }
+    }
+}
+
+/// `avatars.yandex.net/get-music-content/<...>/%%`, where `%%` is the size of the image
+fn cover_url(uri: &str) -> String {
+    format!("https://{}", uri.replace("%%", "400x400"))
+}
+
+impl From<AlbumJson> for meta::Album {
+    fn from(json: AlbumJson) -> Self {
+        Self {
+            id: meta::AlbumId::yandex(json.id),
+            title: json.title,
+            artists: json.artists.into_iter().map(Into::into).collect(),
+            year: json.year,
+            version: json.version,
+            track_count: json.track_count,
+            genre: json.genre,
+            label: json.labels.into_iter().next().map(LabelJson::name),
+            cover_url: json.cover_uri.as_deref().map(cover_url),
+        }
+    }
+}
+
+/// Album of the track with the position of the track there
+#[derive(Deserialize)]
+struct AlbumRefJson {
+    #[serde(deserialize_with = "id")]
+    id: u32,
+    title: Option<String>,
+    #[serde(rename = "trackPosition")]
+    track_position: Option<TrackPositionJson>,
+}
+
+#[derive(Deserialize)]
+struct TrackPositionJson {
+    volume: u16,
+    index: u16,
+}
+
+// {"id":"57703","title":"Time","durationMs":413000,"artists":[...],"albums":[{"id":4766,...}]}
+#[derive(Deserialize)]
+struct TrackJson {
+    #[serde(deserialize_with = "id")]
+    id: u32,
+    title: String,
+    #[serde(default)]
+    artists: Vec<ArtistJson>,
+    #[serde(default)]
+    albums: Vec<AlbumRefJson>,
+    #[serde(rename = "durationMs")]
+    duration_ms: Option<u64>,
+    available: Option<bool>,
+    /// `explicit` for the explicit tracks
+    #[serde(rename = "contentWarning")]
+    content_warning: Option<String>,
+}
+
+impl TryFrom<TrackJson> for meta::Track {
+    type Error = ();
+
+    /// Tracks without an album cannot be played
+    fn try_from(json: TrackJson) -> StdResult<Self, Self::Error> {
+        let album = json.albums.into_iter().next().ok_or(())?;
+        let position = album.track_position;
+        Ok(Self {
+            album_id: meta::AlbumId::yandex(album.id),
+            track_id: meta::TrackId::yandex(json.id),
+            name: json.title,
+            artists: Arc::new(json.artists.into_iter().map(Into::into).collect()),
+            duration: json.duration_ms.map(Duration::from_millis),
+            album_title: album.title,
+            disc: position.as_ref().map(|position| position.volume),
+            number: position.as_ref().map(|position| position.index),
+            explicit: json.content_warning.as_deref() == Some("explicit"),
+            available: json.available.unwrap_or(true),
+        })
+    }
+}
+
+fn tracks(tracks: Vec<TrackJson>) -> meta::Tracks {
+    meta::Tracks {
+        tracks: tracks
+            .into_iter()
+            .filter_map(|track| track.try_into().ok())
+            .collect(),
+    }
+}
+
+#[derive(Deserialize)]
+struct Items<T> {
+    items: Vec<T>,
+}
+
+impl<T> Default for Items<T> {
+    fn default() -> Self {
+        Self { items: vec![] }
+    }
+}
+
+/// Answer of `music-search.jsx`, only the requested type of the items is present
+#[derive(Deserialize)]
+struct SearchJson {
+    #[serde(default)]
+    artists: Items<ArtistJson>,
+    #[serde(default)]
+    albums: Items<AlbumJson>,
+    #[serde(default)]
+    tracks: Items<TrackJson>,
+    #[serde(default)]
+    playlists: Items<PlaylistJson>,
+}
+
+/// Answer of `artist.jsx`, albums, tracks or playlists are present depending on the request
+#[derive(Deserialize)]
+struct ArtistPageJson {
+    #[serde(default)]
+    albums: Vec<AlbumJson>,
+    #[serde(default)]
+    tracks: Vec<TrackJson>,
+    #[serde(default)]
+    playlists: Vec<PlaylistJson>,
+}
+
+/// Answer of `album.jsx`, the tracks are split by discs
+#[derive(Deserialize)]
+struct AlbumPageJson {
+    #[serde(default)]
+    volumes: Vec<Vec<TrackJson>>,
+}
+
+// {"uid":123,"kind":3,"title":"Favorites","trackCount":10,"owner":{"uid":123,"login":"user"}}
+#[derive(Deserialize)]
+struct PlaylistJson {
+    kind: u32,
+    title: String,
+    #[serde(rename = "trackCount", default)]
+    track_count: u32,
+    owner: OwnerJson,
+}
+
+#[derive(Deserialize)]
+struct OwnerJson {
+    login: String,
+}
+
+impl From<PlaylistJson> for meta::Playlist {
+    fn from(json: PlaylistJson) -> Self {
+        Self {
+            url: format!("/users/{}/playlists/{}", json.owner.login, json.kind),
+            owner: json.owner.login,
+            title: json.title,
+            track_count: json.track_count,
+        }
+    }
+}
+
+/// Answers of the api are wrapped into `{"invocationInfo":{...},"result":...}`
+#[derive(Deserialize)]
+struct Answer<T> {
+    result: T,
+}
+
+#[derive(Deserialize)]
+struct AccountStatusJson {
+    account: AccountJson,
+}
+
+#[derive(Deserialize)]
+struct AccountJson {
+    uid: u64,
+    #[serde(default)]
+    login: String,
+}
+
+/// Answer of `likes/tracks`, only the ids of the tracks are there
+#[derive(Deserialize)]
+struct LikedTracksJson {
+    library: TrackIdsJson,
+}
+
+#[derive(Deserialize)]
+struct TrackIdsJson {
+    #[serde(default)]
+    tracks: Vec<TrackRefJson>,
+}
+
+#[derive(Deserialize)]
+struct TrackRefJson {
+    #[serde(deserialize_with = "id")]
+    id: u32,
+}
+
+/// Item of `likes/albums?rich=true`, the removed albums come without the album
+#[derive(Deserialize)]
+struct LikedAlbumJson {
+    album: Option<AlbumJson>,
+}
+
+/// Answer of `playlists/<kind>`
+#[derive(Deserialize)]
+struct PlaylistTracksJson {
+    #[serde(default)]
+    tracks: Vec<PlaylistTrackJson>,
+}
+
+#[derive(Deserialize)]
+struct PlaylistTrackJson {
+    track: Option<TrackJson>,
+}
+
+/// Answer of `artists/<id>/brief-info`
+#[derive(Deserialize)]
+struct ArtistBriefJson {
+    artist: ArtistInfoJson,
+    #[serde(rename = "popularTracks", default)]
+    popular_tracks: Vec<TrackJson>,
+    #[serde(default)]
+    albums: Vec<AlbumJson>,
+    #[serde(rename = "alsoAlbums", default)]
+    also_albums: Vec<AlbumJson>,
+    #[serde(rename = "similarArtists", default)]
+    similar_artists: Vec<ArtistJson>,
+}
+
+#[derive(Deserialize)]
+struct ArtistInfoJson {
+    name: Option<String>,
+    #[serde(default)]
+    genres: Vec<String>,
+    description: Option<DescriptionJson>,
+}
+
+#[derive(Deserialize)]
+struct DescriptionJson {
+    text: String,
+}
+
+/// Answer of `rotor/station/<id>/tracks`
+#[derive(Deserialize)]
+struct StationTracksJson {
+    #[serde(rename = "batchId")]
+    batch_id: String,
+    #[serde(default)]
+    sequence: Vec<SequenceItemJson>,
+}
+
+#[derive(Deserialize)]
+struct SequenceItemJson {
+    track: Option<TrackJson>,
+}
+
+fn parse<'a, T: Deserialize<'a>>(body: &'a str) -> Result<T> {
+    serde_json::from_str(body).context(JsonError { body })
+}
+
+fn parse_answer<'a, T: Deserialize<'a>>(body: &'a str) -> Result<T> {
+    parse::<Answer<T>>(body).map(|answer| answer.result)
+}
+
+pub fn search_url(kind: &str, text: &str, page: usize) -> String {
+    let params = [
+        ("text", text.to_string()),
+        ("type", kind.to_string()),
+        ("page", page.to_string()),
+    ];
+    reqwest::Url::parse_with_params(&format!("{}/handlers/music-search.jsx", BASE_URL), &params)
+        .expect("search url is always valid")
+        .to_string()
+}
+
+/// Handler url of the artist albums, tracks or playlists, e.g. `what` is `albums`
+pub fn artist_url(artist: meta::ArtistId, what: &str) -> String {
+    format!(
+        "{}/handlers/artist.jsx?artist={}&what={}",
+        BASE_URL, artist, what
+    )
+}
+
+pub fn album_url(album: meta::AlbumId) -> String {
+    format!("{}/handlers/album.jsx?album={}", BASE_URL, album)
+}
+
+pub fn account_url() -> String {
+    format!("{}/account/status", API_URL)
+}
+
+/// Api url of the library section, e.g. `likes/tracks` or `playlists/list`
+pub fn library_url(account: &meta::Account, section: &str) -> String {
+    format!("{}/users/{}/{}", API_URL, account.uid, section)
+}
+
+/// Api url of the tracks with the posted ids
+pub fn tracks_url() -> String {
+    format!("{}/tracks", API_URL)
+}
+
+pub fn playlist_url(playlist: &meta::Playlist) -> String {
+    format!("{}{}", API_URL, playlist.url)
+}
+
+/// Section of the likes and the form field with the ids of the item, e.g. `track-ids`
+pub fn like_params(item: &meta::Likeable) -> (&'static str, &'static str, String) {
+    match item {
+        meta::Likeable::Track(track) => (
+            "tracks",
+            "track-ids",
+            format!("{}:{}", track.track_id, track.album_id),
+        ),
+        meta::Likeable::Album(album) => ("albums", "album-ids", album.id.to_string()),
+        meta::Likeable::Artist(artist) => ("artists", "artist-ids", artist.id.to_string()),
+    }
+}
+
+/// Api url of the next tracks of the station, the last played track is passed as `queue`
+pub fn station_tracks_url(station: &meta::Station, last: Option<meta::TrackId>) -> String {
+    let url = format!(
+        "{}/rotor/station/{}/tracks?settings2=true",
+        API_URL, station.id
+    );
+    match last {
+        Some(track_id) => format!("{}&queue={}", url, track_id),
+        None => url,
+    }
+}
+
+pub fn station_feedback_url(station: &meta::Station, batch_id: &str) -> String {
+    format!(
+        "{}/rotor/station/{}/feedback?batch-id={}",
+        API_URL, station.id, batch_id
+    )
+}
+
+/// Body of the station feedback, e.g. `{"type":"skip","trackId":"57703",...}`
+pub fn feedback_body(feedback: Feedback) -> serde_json::Value {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    let mut body = serde_json::json!({
+        "from": "rum-player",
+        "timestamp": timestamp,
+    });
+    let (kind, track_id, played) = match feedback {
+        Feedback::RadioStarted => ("radioStarted", None, None),
+        Feedback::TrackFinished { track_id, played } => {
+            ("trackFinished", Some(track_id), Some(played))
+        }
+        Feedback::Skip { track_id, played } => ("skip", Some(track_id), Some(played)),
+        Feedback::Like { track_id } => ("like", Some(track_id), None),
+    };
+    body["type"] = kind.into();
+    if let Some(track_id) = track_id {
+        body["trackId"] = track_id.to_string().into();
+    }
+    if let Some(played) = played {
+        body["totalPlayedSeconds"] = played.as_secs_f64().into();
+    }
+    body
+}
+
+pub fn parse_station_tracks(body: &str) -> Result<meta::StationTracks> {
+    let station: StationTracksJson = parse_answer(body)?;
+    Ok(meta::StationTracks {
+        batch_id: station.batch_id,
+        tracks: tracks(
+            station
+                .sequence
+                .into_iter()
+                .filter_map(|item| item.track)
+                .collect(),
+        )
+        .tracks,
+    })
+}
+
+pub fn artist_brief_url(artist: meta::ArtistId) -> String {
+    format!("{}/artists/{}/brief-info", API_URL, artist)
+}
+
+pub fn parse_artist_page(artist: &meta::Artist, body: &str) -> Result<meta::ArtistPage> {
+    let brief: ArtistBriefJson = parse_answer(body)?;
+    let mut page = meta::ArtistPage {
+        // the artists opened by the links have no name yet
+        artist: meta::Artist {
+            id: artist.id,
+            name: brief.artist.name.unwrap_or_else(|| artist.name.clone()),
+        },
+        bio: brief.artist.description.map(|description| description.text),
+        genres: brief.artist.genres,
+        popular_tracks: tracks(brief.popular_tracks).tracks,
+        albums: vec![],
+        singles: vec![],
+        compilations: vec![],
+        appearances: brief.also_albums.into_iter().map(Into::into).collect(),
+        similar: brief.similar_artists.into_iter().map(Into::into).collect(),
+    };
+    for album in brief.albums {
+        let section = match album.kind.as_deref() {
+            Some("single") => &mut page.singles,
+            Some("compilation") => &mut page.compilations,
+            _ => &mut page.albums,
+        };
+        section.push(album.into());
+    }
+    Ok(page)
+}
+
+pub fn parse_account(body: &str) -> Result<meta::Account> {
+    let status: AccountStatusJson = parse_answer(body)?;
+    Ok(meta::Account {
+        uid: status.account.uid,
+        login: status.account.login,
+    })
+}
+
+/// Ids of the liked tracks, the tracks themselves are requested by them
+pub fn parse_liked_track_ids(body: &str) -> Result<Vec<meta::TrackId>> {
+    let likes: LikedTracksJson = parse_answer(body)?;
+    Ok(likes
+        .library
+        .tracks
+        .into_iter()
+        .map(|track| meta::TrackId::yandex(track.id))
+        .collect())
+}
+
+pub fn parse_tracks_by_ids(body: &str) -> Result<meta::Tracks> {
+    Ok(tracks(parse_answer(body)?))
+}
+
+pub fn parse_liked_albums(body: &str) -> Result<meta::Albums> {
+    let likes: Vec<LikedAlbumJson> = parse_answer(body)?;
+    Ok(meta::Albums {
+        albums: likes
+            .into_iter()
+            .filter_map(|liked| liked.album)
+            .map(Into::into)
+            .collect(),
+    })
+}
+
+pub fn parse_liked_artists(body: &str) -> Result<meta::Artists> {
+    let artists: Vec<ArtistJson> = parse_answer(body)?;
+    Ok(meta::Artists {
+        artists: artists.into_iter().map(Into::into).collect(),
+    })
+}
+
+pub fn parse_playlists(body: &str) -> Result<meta::Playlists> {
+    let playlists: Vec<PlaylistJson> = parse_answer(body)?;
+    Ok(meta::Playlists {
+        playlists: playlists.into_iter().map(Into::into).collect(),
+    })
+}
+
+pub fn parse_playlist_tracks(body: &str) -> Result<meta::Tracks> {
+    let playlist: PlaylistTracksJson = parse_answer(body)?;
+    Ok(tracks(
+        playlist
+            .tracks
+            .into_iter()
+            .filter_map(|item| item.track)
+            .collect(),
+    ))
+}
+
+pub fn parse_artists(body: &str) -> Result<meta::Artists> {
+    let search: SearchJson = parse(body)?;
+    Ok(meta::Artists {
+        artists: search.artists.items.into_iter().map(Into::into).collect(),
+    })
+}
+
+pub fn parse_albums(body: &str) -> Result<meta::Albums> {
+    let search: SearchJson = parse(body)?;
+    Ok(meta::Albums {
+        albums: search.albums.items.into_iter().map(Into::into).collect(),
+    })
+}
+
+pub fn parse_tracks(body: &str) -> Result<meta::Tracks> {
+    let search: SearchJson = parse(body)?;
+    Ok(tracks(search.tracks.items))
+}
+
+pub fn parse_playlist_search(body: &str) -> Result<meta::Playlists> {
+    let search: SearchJson = parse(body)?;
+    Ok(meta::Playlists {
+        playlists: search.playlists.items.into_iter().map(Into::into).collect(),
+    })
+}
+
+pub fn parse_artist_playlists(body: &str) -> Result<meta::Playlists> {
+    let page: ArtistPageJson = parse(body)?;
+    Ok(meta::Playlists {
+        playlists: page.playlists.into_iter().map(Into::into).collect(),
+    })
+}
+
+pub fn parse_artist_albums(body: &str) -> Result<meta::Albums> {
+    let page: ArtistPageJson = parse(body)?;
+    Ok(meta::Albums {
+        albums: page.albums.into_iter().map(Into::into).collect(),
+    })
+}
+
+pub fn parse_artist_tracks(body: &str) -> Result<meta::Tracks> {
+    let page: ArtistPageJson = parse(body)?;
+    Ok(tracks(page.tracks))
+}
+
+pub fn parse_album_tracks(body: &str) -> Result<meta::Tracks> {
+    let page: AlbumPageJson = parse(body)?;
+    Ok(tracks(page.volumes.into_iter().flatten().collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_search() {
+        let body = r#"{"text":"time","tracks":{"total":1,"items":[
+            {"id":"57703","title":"Time","artists":[{"id":79215,"name":"Pink Floyd"}],
+             "albums":[{"id":4766,"title":"The Dark Side of the Moon"}]},
+            {"id":"1","title":"No album","artists":[],"albums":[]}
+        ]}}"#;
+        let tracks = parse_tracks(body).unwrap().tracks;
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].album_id, meta::AlbumId::yandex(4766));
+        assert_eq!(tracks[0].track_id, meta::TrackId::yandex(57703));
+        assert_eq!(tracks[0].artists[0].id, meta::ArtistId::yandex(79215));
+        assert!(parse_albums(body).unwrap().albums.is_empty());
+
+        let body = r#"{"playlists":{"items":[{"kind":1000,"title":"Rock hits",
+            "trackCount":50,"owner":{"login":"music-blog"}}]}}"#;
+        let playlists = parse_playlist_search(body).unwrap().playlists;
+        assert_eq!(playlists[0].url, "/users/music-blog/playlists/1000");
+    }
+
+    #[test]
+    fn test_parse_album() {
+        let body = r#"{"id":4766,"title":"The Dark Side of the Moon","year":1973,
+            "volumes":[[{"id":57702,"title":"Breathe","albums":[{"id":4766}]}],
+                       [{"id":57703,"title":"Time","albums":[{"id":4766}]}]]}"#;
+        let body_with_details = r#"{"volumes":[[{"id":57703,"title":"Time","durationMs":413000,
+            "available":false,"contentWarning":"explicit",
+            "albums":[{"id":4766,"title":"The Dark Side of the Moon",
+                       "trackPosition":{"volume":1,"index":4}}]}]]}"#;
+        let track = parse_album_tracks(body_with_details)
+            .unwrap()
+            .tracks
+            .remove(0);
+        assert_eq!(track.duration, Some(Duration::from_secs(413)));
+        assert_eq!(
+            track.album_title.as_deref(),
+            Some("The Dark Side of the Moon")
+        );
+        assert_eq!((track.disc, track.number), (Some(1), Some(4)));
+        assert!(track.explicit && !track.available);
+
+        let body_with_album = r#"{"albums":[{"id":4766,"title":"The Dark Side of the Moon",
+            "year":1973,"trackCount":10,"genre":"rock","labels":[{"id":1,"name":"EMI"}],
+            "coverUri":"avatars.yandex.net/get-music-content/1/%%"}]}"#;
+        let album = parse_artist_albums(body_with_album)
+            .unwrap()
+            .albums
+            .remove(0);
+        assert_eq!(
+            (album.track_count, album.label.as_deref()),
+            (Some(10), Some("EMI"))
+        );
+        assert_eq!(
+            album.cover_url.as_deref(),
+            Some("https://avatars.yandex.net/get-music-content/1/400x400")
+        );
+
+        let names = parse_album_tracks(body)
+            .unwrap()
+            .tracks
+            .into_iter()
+            .map(|track| track.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Breathe", "Time"]);
+        assert!(parse_album_tracks("<html>").is_err());
+    }
+
+    #[test]
+    fn test_parse_library() {
+        let body = r#"{"invocationInfo":{},"result":{"library":{"uid":1,"tracks":[
+            {"id":"57703","albumId":"4766","timestamp":"2020-01-01T00:00:00+00:00"}]}}}"#;
+        assert_eq!(
+            parse_liked_track_ids(body).unwrap(),
+            [meta::TrackId::yandex(57703)]
+        );
+
+        let body = r#"{"result":[{"uid":1,"kind":3,"title":"Favorites","trackCount":10,
+            "owner":{"uid":1,"login":"user"}}]}"#;
+        let playlists = parse_playlists(body).unwrap().playlists;
+        assert_eq!(playlists[0].url, "/users/user/playlists/3");
+        assert_eq!(playlists[0].track_count, 10);
+
+        let body = r#"{"result":[{"timestamp":"x","album":{"id":4766,"title":"Meddle"}},
+            {"timestamp":"y"}]}"#;
+        let albums = parse_liked_albums(body).unwrap().albums;
+        assert_eq!(albums.len(), 1);
+        assert_eq!(albums[0].id, meta::AlbumId::yandex(4766));
+    }
+
+    #[test]
+    fn test_parse_artist_tracks() {
+        let artist = meta::Artist {
+            id: meta::ArtistId::yandex(79215),
+            name: "Pink Floyd".to_string(),
+        };
+        let body = r#"{"tracks":[{"id":"57703","title":"Time",
+            "artists":[{"id":79215,"name":"Pink Floyd"}],"albums":[{"id":4766}]},
+            {"id":"57704","title":"Money","artists":[],"albums":[{"id":4766}]}]}"#;
+        let tracks: Vec<_> = parse_artist_tracks(body)
+            .unwrap()
+            .tracks
+            .into_iter()
+            .map(|track| track.with_artist(&artist))
+            .collect();
+        assert_eq!(tracks[0].artists.len(), 1);
+        assert_eq!(tracks[1].artists[0].name, "Pink Floyd");
+    }
+
+    #[test]
+    fn test_parse_artist_page() {
+        let artist = meta::Artist {
+            id: meta::ArtistId::yandex(79215),
+            name: "Pink Floyd".to_string(),
+        };
+        let body = r#"{"result":{"artist":{"id":79215,"name":"Pink Floyd",
+            "genres":["rock"],"description":{"text":"English rock band"}},
+            "albums":[{"id":1,"title":"Meddle","year":1971},
+                      {"id":2,"title":"Money","year":1973,"type":"single"}],
+            "alsoAlbums":[{"id":3,"title":"Tribute","year":2000}],
+            "similarArtists":[{"id":4,"name":"Genesis"}]}}"#;
+        let page = parse_artist_page(&artist, body).unwrap();
+        assert_eq!(page.bio.as_deref(), Some("English rock band"));
+        assert_eq!((page.albums.len(), page.singles.len()), (1, 1));
+        assert_eq!(page.appearances[0].title, "Tribute");
+        assert_eq!(page.similar[0].name, "Genesis");
+    }
+
+    #[test]
+    fn test_parse_station() {
+        let body = r#"{"result":{"id":{"type":"genre","tag":"rock"},"batchId":"abc.1",
+            "sequence":[{"type":"track","track":{"id":"57703","title":"Time",
+            "albums":[{"id":4766}]},"liked":false}]}}"#;
+        let station = parse_station_tracks(body).unwrap();
+        assert_eq!(station.batch_id, "abc.1");
+        assert_eq!(station.tracks[0].track_id, meta::TrackId::yandex(57703));
+        assert_eq!(
+            station_tracks_url(
+                &meta::Station::of_genre("rock"),
+                Some(meta::TrackId::yandex(57703))
+            ),
+            "https://api.music.yandex.net/rotor/station/genre:rock/tracks?settings2=true&queue=57703"
+        );
+    }
+
+    #[test]
+    fn test_handler_urls() {
+        let artist = meta::Artist {
+            id: meta::ArtistId::yandex(79215),
+            name: "Pink Floyd".to_string(),
+        };
+        assert_eq!(
+            artist_url(artist.id, "albums"),
+            "https://music.yandex.ru/handlers/artist.jsx?artist=79215&what=albums"
+        );
+        assert_eq!(
+            search_url("tracks", "a&b", 1),
+            "https://music.yandex.ru/handlers/music-search.jsx?text=a%26b&type=tracks&page=1"
+        );
+    }
+}
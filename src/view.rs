@@ -1,29 +1,256 @@
+use std::collections::HashSet;
 use std::ops::{Deref, DerefMut};
+use std::time::Duration;
 
 use derive_more::From;
 
-use crate::meta::{Album, Artist, Track};
+use crate::edit::InsertBuffer;
+use crate::export::Progress;
+use crate::filter::fuzzy_match;
+use crate::jobs::JobId;
+use crate::meta::{self, Album, Artist, Track, TrackId};
+
+pub fn artist_label(artist: &Artist) -> String {
+    artist.name.clone()
+}
+
+pub fn album_label(album: &Album) -> String {
+    let artist = album
+        .artists
+        .first()
+        .map(|a| a.name.as_str())
+        .unwrap_or("unknown");
+    let mut details = format!("year: {}", album.year);
+    if let Some(ref version) = album.version {
+        details.push_str(&format!(", {}", version));
+    }
+    if let Some(count) = album.track_count {
+        details.push_str(&format!(", {} tracks", count));
+    }
+    format!("{}: {} ({})", artist, album.title, details)
+}
+
+/// `m:ss` or `h:mm:ss` for the longer ones
+pub fn duration_label(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+pub fn track_label(track: &Track) -> String {
+    with_track_details(track, track_title(track))
+}
+
+/// Track label with the album, since the queue mixes the tracks of different albums
+pub fn queue_label(track: &Track) -> String {
+    let title = match track.album_title {
+        Some(ref album) => format!("{} - {}", track_title(track), album),
+        None => track_title(track),
+    };
+    with_track_details(track, title)
+}
+
+fn track_title(track: &Track) -> String {
+    format!(
+        "{} ({})",
+        track.name,
+        itertools::join(track.artists.iter().map(|a| a.name.as_str()), ", ")
+    )
+}
+
+fn with_track_details(track: &Track, mut label: String) -> String {
+    if track.explicit {
+        label.push_str(" [E]");
+    }
+    if !track.available {
+        label.push_str(" [unavailable]");
+    }
+    if let Some(duration) = track.duration {
+        label.push_str(&format!(" {}", duration_label(duration)));
+    }
+    label
+}
+
+pub fn playlist_label(playlist: &meta::Playlist) -> String {
+    // scraped pages might have no track count
+    if playlist.track_count == 0 {
+        format!("{}: {}", playlist.owner, playlist.title)
+    } else {
+        format!(
+            "{}: {} ({} tracks)",
+            playlist.owner, playlist.title, playlist.track_count
+        )
+    }
+}
+
+pub fn destination_label(destination: &Destination) -> String {
+    match destination {
+        Destination::Chart => "Chart".to_string(),
+        Destination::NewReleases => "New releases".to_string(),
+        Destination::NewPlaylists => "New playlists".to_string(),
+        Destination::Genres => "Genres".to_string(),
+        Destination::GenreTracks(genre) => format!("Popular tracks of {}", genre.name),
+        Destination::GenreAlbums(genre) => format!("Albums of {}", genre.name),
+    }
+}
+
+pub fn menu_label(entry: &MenuEntry) -> String {
+    match entry {
+        MenuEntry::List(destination) => destination_label(destination),
+        MenuEntry::Genre(genre) => genre.name.clone(),
+        MenuEntry::GenreRadio(genre) => format!("Radio of {}", genre.name),
+    }
+}
+
+pub fn export_label(progress: &Progress) -> String {
+    let mut label = format!("{}: {}/{}", progress.name, progress.done, progress.total);
+    if progress.failed > 0 {
+        label.push_str(&format!(", {} failed", progress.failed));
+    }
+    label
+}
 
 #[derive(Debug, Clone, Default)]
 pub struct MainView {
-    insert_buffer: String,
+    insert_buffer: InsertBuffer,
     view: View,
+    help: Option<Help>,
+    prompt: Option<Prompt>,
+    jobs: Vec<JobStatus>,
+    /// Cursor of the job list, if it is shown
+    job_list: Option<usize>,
+    spinner: usize,
+    /// Ids of the downloaded tracks
+    offline: HashSet<TrackId>,
+    /// Generation of the track cache, which the ids are copied at
+    offline_generation: Option<usize>,
+    logged_in: bool,
+    /// Whether the login gives the library, the cookie ones do not
+    library: bool,
 }
 
 impl MainView {
+    pub fn logged_in(&self) -> bool {
+        self.logged_in
+    }
+
+    pub fn set_logged_in(&mut self, logged_in: bool) {
+        self.logged_in = logged_in;
+    }
+
+    pub fn library(&self) -> bool {
+        self.library
+    }
+
+    pub fn set_library(&mut self, library: bool) {
+        self.library = library;
+    }
+
+    pub fn offline(&self) -> &HashSet<TrackId> {
+        &self.offline
+    }
+
+    pub fn offline_generation(&self) -> Option<usize> {
+        self.offline_generation
+    }
+
+    pub fn set_offline(&mut self, generation: usize, offline: HashSet<TrackId>) {
+        self.offline_generation = Some(generation);
+        self.offline = offline;
+    }
+
+    pub fn jobs(&self) -> &[JobStatus] {
+        &self.jobs
+    }
+
+    pub fn set_jobs(&mut self, jobs: Vec<JobStatus>) {
+        if let Some(cursor) = self.job_list.as_mut() {
+            *cursor = (*cursor).min(jobs.len().saturating_sub(1));
+        }
+        self.jobs = jobs;
+    }
+
+    /// Spinner char, if a job started at the current view is still running
+    pub fn spinner(&self) -> Option<char> {
+        let name = self.view.name();
+        if self.jobs.iter().any(|job| job.view == name) {
+            Some(SPINNER[self.spinner % SPINNER.len()])
+        } else {
+            None
+        }
+    }
+
+    pub fn tick_spinner(&mut self) {
+        self.spinner = self.spinner.wrapping_add(1);
+    }
+
+    pub fn job_list(&self) -> Option<usize> {
+        self.job_list
+    }
+
+    pub fn job_list_mut(&mut self) -> Option<&mut usize> {
+        self.job_list.as_mut()
+    }
+
+    pub fn show_job_list(&mut self) {
+        self.job_list = Some(0);
+    }
+
+    pub fn hide_job_list(&mut self) -> Option<usize> {
+        self.job_list.take()
+    }
+
+    pub fn help(&self) -> Option<&Help> {
+        self.help.as_ref()
+    }
+
+    pub fn help_mut(&mut self) -> Option<&mut Help> {
+        self.help.as_mut()
+    }
+
+    pub fn show_help(&mut self, help: Help) {
+        self.help = Some(help);
+    }
+
+    pub fn hide_help(&mut self) -> Option<Help> {
+        self.help.take()
+    }
+
+    pub fn prompt(&self) -> Option<&Prompt> {
+        self.prompt.as_ref()
+    }
+
+    pub fn prompt_mut(&mut self) -> Option<&mut Prompt> {
+        self.prompt.as_mut()
+    }
+
+    pub fn open_prompt(&mut self, kind: PromptKind) {
+        self.prompt = Some(Prompt {
+            kind,
+            buffer: String::new(),
+        });
+    }
+
+    pub fn close_prompt(&mut self) -> Option<Prompt> {
+        self.prompt.take()
+    }
+
     pub fn replace_view(&mut self, view: View) -> View {
         std::mem::replace(&mut self.view, view)
     }
 
-    pub fn insert_buffer(&self) -> &str {
+    pub fn insert_buffer(&self) -> &InsertBuffer {
         &self.insert_buffer
     }
 
-    pub fn insert_buffer_mut(&mut self) -> &mut String {
+    pub fn insert_buffer_mut(&mut self) -> &mut InsertBuffer {
         &mut self.insert_buffer
     }
 
-    pub fn view_and_buffer_mut(&mut self) -> (&mut View, &mut String) {
+    pub fn view_and_buffer_mut(&mut self) -> (&mut View, &mut InsertBuffer) {
         (&mut self.view, &mut self.insert_buffer)
     }
 
@@ -50,6 +277,9 @@ impl DerefMut for MainView {
 pub struct ArtistSearch {
     pub cached_artists: Vec<Artist>,
     pub cursor: usize,
+    pub filter: Option<Filter>,
+    pub pages: Option<Pages>,
+    pub origin: Option<Origin>,
 }
 
 impl From<Vec<Artist>> for ArtistSearch {
@@ -57,6 +287,9 @@ impl From<Vec<Artist>> for ArtistSearch {
         Self {
             cached_artists: artists,
             cursor: 0,
+            filter: None,
+            pages: None,
+            origin: None,
         }
     }
 }
@@ -65,6 +298,9 @@ impl From<Vec<Artist>> for ArtistSearch {
 pub struct AlbumSearch {
     pub cached_albums: Vec<Album>,
     pub cursor: usize,
+    pub filter: Option<Filter>,
+    pub pages: Option<Pages>,
+    pub origin: Option<Origin>,
 }
 
 impl From<Vec<Album>> for AlbumSearch {
@@ -72,6 +308,9 @@ impl From<Vec<Album>> for AlbumSearch {
         Self {
             cached_albums: albums,
             cursor: 0,
+            filter: None,
+            pages: None,
+            origin: None,
         }
     }
 }
@@ -80,6 +319,9 @@ impl From<Vec<Album>> for AlbumSearch {
 pub struct TrackList {
     pub cached_tracks: Vec<Track>,
     pub cursor: usize,
+    pub filter: Option<Filter>,
+    pub pages: Option<Pages>,
+    pub origin: Option<Origin>,
 }
 
 impl From<Vec<Track>> for TrackList {
@@ -87,6 +329,251 @@ impl From<Vec<Track>> for TrackList {
         Self {
             cached_tracks: tracks,
             cursor: 0,
+            filter: None,
+            pages: None,
+            origin: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PlaylistSearch {
+    pub cached_playlists: Vec<meta::Playlist>,
+    pub cursor: usize,
+    pub filter: Option<Filter>,
+    pub pages: Option<Pages>,
+    pub origin: Option<Origin>,
+}
+
+impl From<Vec<meta::Playlist>> for PlaylistSearch {
+    fn from(playlists: Vec<meta::Playlist>) -> Self {
+        Self {
+            cached_playlists: playlists,
+            cursor: 0,
+            filter: None,
+            pages: None,
+            origin: None,
+        }
+    }
+}
+
+/// List, which is loaded when it is opened from a menu
+#[derive(Debug, Clone)]
+pub enum Destination {
+    Chart,
+    NewReleases,
+    NewPlaylists,
+    Genres,
+    GenreTracks(meta::Genre),
+    GenreAlbums(meta::Genre),
+}
+
+/// Item of a menu, only the lists are loaded, the other items are opened at once
+#[derive(Debug, Clone)]
+pub enum MenuEntry {
+    List(Destination),
+    /// Landing of the genre, i.e. a menu with its sections
+    Genre(meta::Genre),
+    GenreRadio(meta::Genre),
+}
+
+/// List of destinations, e.g. the start menu or genres
+#[derive(Debug, Clone)]
+pub struct Menu {
+    pub title: String,
+    pub items: Vec<MenuEntry>,
+    pub cursor: usize,
+    pub filter: Option<Filter>,
+    pub origin: Option<Origin>,
+    /// Whether it is the start menu, which is toggled by its hotkey
+    pub is_start: bool,
+}
+
+impl Menu {
+    pub fn create(title: String, items: Vec<MenuEntry>) -> Self {
+        Self {
+            title,
+            items,
+            cursor: 0,
+            filter: None,
+            origin: None,
+            is_start: false,
+        }
+    }
+
+    /// Discovery lists, which are available with no search
+    pub fn start() -> Self {
+        let lists = vec![
+            Destination::Chart,
+            Destination::NewReleases,
+            Destination::NewPlaylists,
+            Destination::Genres,
+        ];
+        Self {
+            is_start: true,
+            ..Self::create(
+                "Start".to_string(),
+                lists.into_iter().map(MenuEntry::List).collect(),
+            )
+        }
+    }
+
+    pub fn genre(genre: meta::Genre) -> Self {
+        Self::create(
+            genre.name.clone(),
+            vec![
+                MenuEntry::List(Destination::GenreTracks(genre.clone())),
+                MenuEntry::List(Destination::GenreAlbums(genre.clone())),
+                MenuEntry::GenreRadio(genre),
+            ],
+        )
+    }
+}
+
+/// Part of the artist page, which is opened as a separate list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtistSection {
+    PopularTracks,
+    Albums,
+    Singles,
+    Compilations,
+    Appearances,
+    Similar,
+}
+
+/// Bio of the artist and the sections of its discography
+#[derive(Debug, Clone)]
+pub struct ArtistPage {
+    pub page: meta::ArtistPage,
+    /// Non-empty sections only
+    pub sections: Vec<ArtistSection>,
+    pub cursor: usize,
+    pub filter: Option<Filter>,
+    pub origin: Option<Origin>,
+}
+
+impl From<meta::ArtistPage> for ArtistPage {
+    fn from(page: meta::ArtistPage) -> Self {
+        let sections = [
+            ArtistSection::PopularTracks,
+            ArtistSection::Albums,
+            ArtistSection::Singles,
+            ArtistSection::Compilations,
+            ArtistSection::Appearances,
+            ArtistSection::Similar,
+        ]
+        .iter()
+        .copied()
+        .filter(|section| section_len(&page, *section) > 0)
+        .collect();
+        Self {
+            page,
+            sections,
+            cursor: 0,
+            filter: None,
+            origin: None,
+        }
+    }
+}
+
+impl ArtistPage {
+    pub fn section_label(&self, section: ArtistSection) -> String {
+        let name = match section {
+            ArtistSection::PopularTracks => "Popular tracks",
+            ArtistSection::Albums => "Albums",
+            ArtistSection::Singles => "Singles",
+            ArtistSection::Compilations => "Compilations",
+            ArtistSection::Appearances => "Appears on",
+            ArtistSection::Similar => "Similar artists",
+        };
+        format!("{} ({})", name, section_len(&self.page, section))
+    }
+
+    /// List with the items of the section
+    pub fn section_view(&self, section: ArtistSection) -> View {
+        let page = &self.page;
+        match section {
+            ArtistSection::PopularTracks => TrackList::from(page.popular_tracks.clone()).into(),
+            ArtistSection::Albums => AlbumSearch::from(page.albums.clone()).into(),
+            ArtistSection::Singles => AlbumSearch::from(page.singles.clone()).into(),
+            ArtistSection::Compilations => AlbumSearch::from(page.compilations.clone()).into(),
+            ArtistSection::Appearances => AlbumSearch::from(page.appearances.clone()).into(),
+            ArtistSection::Similar => ArtistSearch::from(page.similar.clone()).into(),
+        }
+    }
+}
+
+fn section_len(page: &meta::ArtistPage, section: ArtistSection) -> usize {
+    match section {
+        ArtistSection::PopularTracks => page.popular_tracks.len(),
+        ArtistSection::Albums => page.albums.len(),
+        ArtistSection::Singles => page.singles.len(),
+        ArtistSection::Compilations => page.compilations.len(),
+        ArtistSection::Appearances => page.appearances.len(),
+        ArtistSection::Similar => page.similar.len(),
+    }
+}
+
+/// Request, which the list is loaded by, so that it might be refreshed
+#[derive(Debug, Clone)]
+pub enum Origin {
+    Search(String),
+    Artist(Artist),
+    Album(Album),
+    Library(Library),
+    Playlist(meta::Playlist),
+    Destination(Destination),
+}
+
+/// Section of the library of the logged in user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Library {
+    Tracks,
+    Albums,
+    Artists,
+    Playlists,
+}
+
+/// Search results, which are loaded page by page
+#[derive(Debug, Clone)]
+pub struct Pages {
+    pub query: String,
+    /// Number of the page to load next
+    pub next: usize,
+    pub loading: bool,
+}
+
+impl Pages {
+    /// Pages of the query, whose first page is already loaded
+    pub fn new(query: String) -> Self {
+        Self {
+            query,
+            next: 1,
+            loading: false,
+        }
+    }
+}
+
+/// The next page is loaded, when the cursor is that close to the end of the list
+const PRELOAD_DISTANCE: usize = 5;
+
+/// Items of a list view, which are matched by the pattern
+#[derive(Debug, Clone)]
+pub struct Filter {
+    pub pattern: String,
+    pub matches: Vec<usize>,
+}
+
+impl Filter {
+    fn create(pattern: &str, labels: &[String]) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            matches: labels
+                .iter()
+                .enumerate()
+                .filter(|(_, label)| fuzzy_match(pattern, label).is_some())
+                .map(|(i, _)| i)
+                .collect(),
         }
     }
 }
@@ -95,11 +582,113 @@ impl From<Vec<Track>> for TrackList {
 pub struct Playlist {
     pub tracks: Vec<Track>,
     pub current: usize,
+    /// Name of the station, which the queue is refilled from
+    pub station: Option<String>,
 }
 
 impl Playlist {
-    pub fn create(tracks: Vec<Track>, current: usize) -> Self {
-        Self { tracks, current }
+    pub fn create(tracks: Vec<Track>, current: usize, station: Option<String>) -> Self {
+        Self {
+            tracks,
+            current,
+            station,
+        }
+    }
+
+    /// Summed length of the tracks with the known durations
+    pub fn total_duration(&self) -> Duration {
+        self.tracks.iter().filter_map(|track| track.duration).sum()
+    }
+}
+
+/// Progress of the exports, it is updated at each redraw
+#[derive(Debug, Clone, Default)]
+pub struct Downloads {
+    pub exports: Vec<Progress>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchKind {
+    Album,
+    Track,
+    Artist,
+    Playlist,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptKind {
+    Command,
+    Filter,
+}
+
+impl PromptKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            PromptKind::Command => "Command",
+            PromptKind::Filter => "Filter",
+        }
+    }
+
+    pub fn prefix(self) -> char {
+        match self {
+            PromptKind::Command => ':',
+            PromptKind::Filter => '/',
+        }
+    }
+}
+
+const SPINNER: &[char] = &['|', '/', '-', '\\'];
+
+/// Background job, displayed while it is running
+#[derive(Debug, Clone)]
+pub struct JobStatus {
+    pub id: JobId,
+    pub name: String,
+    pub view: &'static str,
+    pub elapsed: Duration,
+}
+
+/// Single line input, displayed instead of the insert buffer
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub buffer: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct HelpSection {
+    pub title: &'static str,
+    pub bindings: Vec<(&'static str, Vec<String>)>,
+}
+
+/// Overlay with key bindings, grouped by a view context
+#[derive(Debug, Clone)]
+pub struct Help {
+    pub sections: Vec<HelpSection>,
+    pub scroll: usize,
+}
+
+impl Help {
+    pub fn create(sections: Vec<HelpSection>) -> Self {
+        Self {
+            sections,
+            scroll: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sections
+            .iter()
+            .map(|section| section.bindings.len() + 1)
+            .sum()
+    }
+
+    pub fn scroll_down(&mut self) {
+        self.scroll = (self.scroll + 1).min(self.len().saturating_sub(1));
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
     }
 }
 
@@ -108,7 +697,11 @@ pub enum View {
     ArtistSearch(ArtistSearch),
     AlbumSearch(AlbumSearch),
     TrackList(TrackList),
+    PlaylistSearch(PlaylistSearch),
+    Menu(Menu),
+    ArtistPage(ArtistPage),
     Playlist(Playlist),
+    Downloads(Downloads),
 }
 
 impl Default for View {
@@ -117,14 +710,47 @@ impl Default for View {
     }
 }
 
+/// Cursor position among the visible items, i.e. only the filtered ones if any
 pub struct CursorMut<'a> {
     cursor: &'a mut usize,
+    position: usize,
+    visible: Option<&'a [usize]>,
     max_cursor: usize,
 }
 
+impl<'a> CursorMut<'a> {
+    fn create(cursor: &'a mut usize, filter: Option<&'a Filter>, len: usize) -> Self {
+        let visible = filter.map(|filter| filter.matches.as_slice());
+        let position = match visible {
+            Some(visible) => visible.iter().position(|i| i == cursor).unwrap_or(0),
+            None => *cursor,
+        };
+        let max_cursor = visible.map(<[_]>::len).unwrap_or(len).saturating_sub(1);
+        Self {
+            cursor,
+            position,
+            visible,
+            max_cursor,
+        }
+    }
+
+    /// Amount of the visible items
+    pub fn len(&self) -> usize {
+        self.max_cursor + 1
+    }
+}
+
 impl Drop for CursorMut<'_> {
     fn drop(&mut self) {
-        *self.cursor = self.max_cursor.min(*self.cursor);
+        let position = self.max_cursor.min(self.position);
+        match self.visible {
+            Some(visible) => {
+                if let Some(cursor) = visible.get(position) {
+                    *self.cursor = *cursor;
+                }
+            }
+            None => *self.cursor = position,
+        }
     }
 }
 
@@ -132,13 +758,13 @@ impl Deref for CursorMut<'_> {
     type Target = usize;
 
     fn deref(&self) -> &Self::Target {
-        &self.cursor
+        &self.position
     }
 }
 
 impl DerefMut for CursorMut<'_> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.cursor
+        &mut self.position
     }
 }
 
@@ -149,7 +775,22 @@ impl View {
             View::ArtistSearch(_) => "ArtistSearch",
             View::AlbumSearch(_) => "AlbumSearch",
             View::TrackList(_) => "TrackList",
+            View::PlaylistSearch(_) => "PlaylistSearch",
+            View::Menu(_) => "Menu",
+            View::ArtistPage(_) => "ArtistPage",
             View::Playlist(_) => "Playlist",
+            View::Downloads(_) => "Downloads",
+        }
+    }
+
+    /// Kind of the search performed at the view
+    pub fn search_kind(&self) -> Option<SearchKind> {
+        match self {
+            View::ArtistSearch(_) => Some(SearchKind::Artist),
+            View::AlbumSearch(_) => Some(SearchKind::Album),
+            View::TrackList(_) => Some(SearchKind::Track),
+            View::PlaylistSearch(_) => Some(SearchKind::Playlist),
+            View::Menu(_) | View::ArtistPage(_) | View::Playlist(_) | View::Downloads(_) => None,
         }
     }
 
@@ -158,30 +799,164 @@ impl View {
             View::ArtistSearch(search) => Some(search.cursor),
             View::AlbumSearch(search) => Some(search.cursor),
             View::TrackList(search) => Some(search.cursor),
-            View::Playlist(_) => None,
+            View::PlaylistSearch(search) => Some(search.cursor),
+            View::Menu(menu) => Some(menu.cursor),
+            View::ArtistPage(page) => Some(page.cursor),
+            View::Playlist(_) | View::Downloads(_) => None,
         }
     }
 
     pub fn cursor_mut(&mut self) -> Option<CursorMut<'_>> {
-        let max_cursor = self.len().saturating_sub(1);
+        let len = self.len();
+
+        match self {
+            View::ArtistSearch(search) => Some(CursorMut::create(
+                &mut search.cursor,
+                search.filter.as_ref(),
+                len,
+            )),
+            View::AlbumSearch(search) => Some(CursorMut::create(
+                &mut search.cursor,
+                search.filter.as_ref(),
+                len,
+            )),
+            View::TrackList(search) => Some(CursorMut::create(
+                &mut search.cursor,
+                search.filter.as_ref(),
+                len,
+            )),
+            View::PlaylistSearch(search) => Some(CursorMut::create(
+                &mut search.cursor,
+                search.filter.as_ref(),
+                len,
+            )),
+            View::Menu(menu) => Some(CursorMut::create(
+                &mut menu.cursor,
+                menu.filter.as_ref(),
+                len,
+            )),
+            View::ArtistPage(page) => Some(CursorMut::create(
+                &mut page.cursor,
+                page.filter.as_ref(),
+                len,
+            )),
+            View::Playlist(_) | View::Downloads(_) => None,
+        }
+    }
+
+    pub fn labels(&self) -> Vec<String> {
+        match self {
+            View::ArtistSearch(search) => search.cached_artists.iter().map(artist_label).collect(),
+            View::AlbumSearch(search) => search.cached_albums.iter().map(album_label).collect(),
+            View::TrackList(search) => search.cached_tracks.iter().map(track_label).collect(),
+            View::PlaylistSearch(search) => {
+                search.cached_playlists.iter().map(playlist_label).collect()
+            }
+            View::Menu(menu) => menu.items.iter().map(menu_label).collect(),
+            View::ArtistPage(page) => page
+                .sections
+                .iter()
+                .map(|section| page.section_label(*section))
+                .collect(),
+            View::Playlist(playlist) => playlist.tracks.iter().map(queue_label).collect(),
+            View::Downloads(downloads) => downloads.exports.iter().map(export_label).collect(),
+        }
+    }
+
+    pub fn filter(&self) -> Option<&Filter> {
+        match self {
+            View::ArtistSearch(search) => search.filter.as_ref(),
+            View::AlbumSearch(search) => search.filter.as_ref(),
+            View::TrackList(search) => search.filter.as_ref(),
+            View::PlaylistSearch(search) => search.filter.as_ref(),
+            View::Menu(menu) => menu.filter.as_ref(),
+            View::ArtistPage(page) => page.filter.as_ref(),
+            View::Playlist(_) | View::Downloads(_) => None,
+        }
+    }
+
+    fn filter_slot(&mut self) -> Option<&mut Option<Filter>> {
+        match self {
+            View::ArtistSearch(search) => Some(&mut search.filter),
+            View::AlbumSearch(search) => Some(&mut search.filter),
+            View::TrackList(search) => Some(&mut search.filter),
+            View::PlaylistSearch(search) => Some(&mut search.filter),
+            View::Menu(menu) => Some(&mut menu.filter),
+            View::ArtistPage(page) => Some(&mut page.filter),
+            View::Playlist(_) | View::Downloads(_) => None,
+        }
+    }
+
+    pub fn pages(&self) -> Option<&Pages> {
+        match self {
+            View::ArtistSearch(search) => search.pages.as_ref(),
+            View::AlbumSearch(search) => search.pages.as_ref(),
+            View::TrackList(search) => search.pages.as_ref(),
+            View::PlaylistSearch(search) => search.pages.as_ref(),
+            View::Menu(_) | View::ArtistPage(_) | View::Playlist(_) | View::Downloads(_) => None,
+        }
+    }
 
+    pub fn pages_mut(&mut self) -> Option<&mut Option<Pages>> {
         match self {
-            View::ArtistSearch(search) => Some(CursorMut {
-                cursor: &mut search.cursor,
-                max_cursor,
-            }),
-            View::AlbumSearch(search) => Some(CursorMut {
-                cursor: &mut search.cursor,
-                max_cursor,
-            }),
-            View::TrackList(search) => Some(CursorMut {
-                cursor: &mut search.cursor,
-                max_cursor,
-            }),
-            View::Playlist(_) => None,
+            View::ArtistSearch(search) => Some(&mut search.pages),
+            View::AlbumSearch(search) => Some(&mut search.pages),
+            View::TrackList(search) => Some(&mut search.pages),
+            View::PlaylistSearch(search) => Some(&mut search.pages),
+            View::Menu(_) | View::ArtistPage(_) | View::Playlist(_) | View::Downloads(_) => None,
         }
     }
 
+    pub fn origin(&self) -> Option<&Origin> {
+        match self {
+            View::ArtistSearch(search) => search.origin.as_ref(),
+            View::AlbumSearch(search) => search.origin.as_ref(),
+            View::TrackList(search) => search.origin.as_ref(),
+            View::PlaylistSearch(search) => search.origin.as_ref(),
+            View::Menu(menu) => menu.origin.as_ref(),
+            View::ArtistPage(page) => page.origin.as_ref(),
+            View::Playlist(_) | View::Downloads(_) => None,
+        }
+    }
+
+    pub fn origin_mut(&mut self) -> Option<&mut Option<Origin>> {
+        match self {
+            View::ArtistSearch(search) => Some(&mut search.origin),
+            View::AlbumSearch(search) => Some(&mut search.origin),
+            View::TrackList(search) => Some(&mut search.origin),
+            View::PlaylistSearch(search) => Some(&mut search.origin),
+            View::Menu(menu) => Some(&mut menu.origin),
+            View::ArtistPage(page) => Some(&mut page.origin),
+            View::Playlist(_) | View::Downloads(_) => None,
+        }
+    }
+
+    /// Query and number of the next page, if the cursor is close to the end of the list.
+    /// The page is marked as loading, so that it is requested once.
+    pub fn request_page(&mut self) -> Option<(String, usize)> {
+        let near_end = self.cursor()? + PRELOAD_DISTANCE >= self.len();
+        let pages = self.pages_mut()?.as_mut()?;
+        if !near_end || pages.loading {
+            return None;
+        }
+        pages.loading = true;
+        Some((pages.query.clone(), pages.next))
+    }
+
+    /// Filters the items and puts the cursor at a matched one
+    pub fn set_filter(&mut self, pattern: &str) {
+        let filter = Filter::create(pattern, &self.labels());
+        if let Some(slot) = self.filter_slot() {
+            *slot = Some(filter);
+        }
+        // cursor is moved to a visible item on drop
+        drop(self.cursor_mut());
+    }
+
+    pub fn clear_filter(&mut self) -> Option<Filter> {
+        self.filter_slot().and_then(Option::take)
+    }
+
     pub fn reset_cursor(&mut self) {
         if let Some(mut cursor) = self.cursor_mut() {
             *cursor = 0;
@@ -193,7 +968,10 @@ impl View {
             View::ArtistSearch(search) => search.cached_artists.len(),
             View::AlbumSearch(search) => search.cached_albums.len(),
             View::TrackList(search) => search.cached_tracks.len(),
-            View::Playlist(_) => 0,
+            View::PlaylistSearch(search) => search.cached_playlists.len(),
+            View::Menu(menu) => menu.items.len(),
+            View::ArtistPage(page) => page.sections.len(),
+            View::Playlist(_) | View::Downloads(_) => 0,
         }
     }
 }